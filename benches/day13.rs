@@ -0,0 +1,85 @@
+use aoc::day13::{compare_raw, part1_raw, part2_raw, Type};
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 20;
+
+fn bench(name: &str, f: impl Fn() -> usize) -> Duration {
+    let mut best = Duration::MAX;
+    let mut result = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = f();
+        best = best.min(start.elapsed());
+    }
+
+    println!("{name:<16} {result:>8} {best:>12.2?}");
+    best
+}
+
+// xorshift, the packets only need to differ, not to be good random numbers
+fn packets(count: usize) -> String {
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    fn packet(next: &mut impl FnMut() -> u64, depth: u32) -> Type {
+        if depth > 0 && !next().is_multiple_of(3) {
+            let length = next() % 6;
+            Type::Array((0..length).map(|_| packet(next, depth - 1)).collect())
+        } else {
+            Type::Value((next() % 11) as i128)
+        }
+    }
+
+    (0..count)
+        .map(|_| Type::Array(vec![packet(&mut next, 5), packet(&mut next, 5)]).to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn main() {
+    let raw = std::fs::read_to_string("input/2022/day13.txt").expect("missing day13 input");
+
+    let tree = bench("part1 tree", || {
+        let packets = raw
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| Type::parse(1, line).expect("invalid day13 input"))
+            .collect::<Vec<Type>>();
+        packets
+            .chunks(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < pair[1])
+            .map(|(index, _)| index + 1)
+            .sum()
+    });
+    let streaming = bench("part1 raw", || part1_raw(&raw));
+    println!(
+        "part1 speedup    {:.1}x",
+        tree.as_secs_f64() / streaming.as_secs_f64()
+    );
+    bench("part2 raw", || part2_raw(&raw));
+
+    let generated = packets(20_000);
+    let tree = bench("sort tree", || {
+        let mut packets = generated
+            .lines()
+            .map(|line| Type::parse(1, line).expect("invalid packet"))
+            .collect::<Vec<Type>>();
+        packets.sort();
+        packets.len()
+    });
+    let streaming = bench("sort raw", || {
+        let mut packets = generated.lines().map(str::as_bytes).collect::<Vec<&[u8]>>();
+        packets.sort_by(|left, right| compare_raw(left, right));
+        packets.len()
+    });
+    println!(
+        "sort speedup     {:.1}x",
+        tree.as_secs_f64() / streaming.as_secs_f64()
+    );
+}
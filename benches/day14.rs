@@ -0,0 +1,42 @@
+use aoc::day14::{parse_input, part1, part1_recursive, part2, part2_analytical, part2_recursive};
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 20;
+
+fn bench(name: &str, f: impl Fn() -> usize) -> Duration {
+    let mut best = Duration::MAX;
+    let mut result = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = f();
+        best = best.min(start.elapsed());
+    }
+
+    println!("{name:<16} {result:>8} {best:>12.2?}");
+    best
+}
+
+fn main() {
+    let raw = std::fs::read_to_string("input/2022/day14.txt").expect("missing day14 input");
+    let input = parse_input(&raw).expect("invalid day14 input");
+
+    let recursive = bench("part1 recursive", || part1_recursive(&input));
+    let iterative = bench("part1 iterative", || part1(&input));
+    println!(
+        "part1 speedup    {:.1}x",
+        recursive.as_secs_f64() / iterative.as_secs_f64()
+    );
+
+    let recursive = bench("part2 recursive", || part2_recursive(&input));
+    let iterative = bench("part2 iterative", || part2(&input));
+    println!(
+        "part2 speedup    {:.1}x",
+        recursive.as_secs_f64() / iterative.as_secs_f64()
+    );
+
+    let analytical = bench("part2 analytical", || part2_analytical(&input));
+    println!(
+        "part2 speedup    {:.1}x",
+        iterative.as_secs_f64() / analytical.as_secs_f64()
+    );
+}
@@ -46,9 +46,9 @@ impl Shape {
     }
 }
 
-impl Into<usize> for Shape {
-    fn into(self) -> usize {
-        match self {
+impl From<Shape> for usize {
+    fn from(shape: Shape) -> usize {
+        match shape {
             Rock => 1,
             Paper => 2,
             Scissors => 3,
@@ -76,9 +76,9 @@ pub enum RoundResult {
     Defeat,
 }
 
-impl Into<usize> for RoundResult {
-    fn into(self) -> usize {
-        match self {
+impl From<RoundResult> for usize {
+    fn from(result: RoundResult) -> usize {
+        match result {
             Win => 6,
             Draw => 3,
             Defeat => 0,
@@ -147,7 +147,7 @@ pub fn part2(input: &str) -> usize {
 
 fn count_score(input: &[(Shape, Shape)]) -> Vec<usize> {
     input
-        .into_iter()
+        .iter()
         .map(|(opponent, me)| {
             <RoundResult as Into<usize>>::into(me.resolve_battle(*opponent))
                 + <Shape as Into<usize>>::into(*me)
@@ -29,7 +29,7 @@ pub fn part1(input: &[(Compartment, Compartment)]) -> u32 {
 #[aoc(day3, part2)]
 pub fn part2(input: &[(Compartment, Compartment)]) -> u32 {
     input
-        .into_iter()
+        .iter()
         .map(|(c1, c2)| c1.0.chars().join(&c2.0).chars().collect::<HashSet<char>>())
         .tuples::<(HashSet<char>, HashSet<char>, HashSet<char>)>()
         .map(|(set1, set2, set3)| {
@@ -42,7 +42,7 @@ pub fn part2(input: &[(Compartment, Compartment)]) -> u32 {
 
 fn find_common_character(input: &[(Compartment, Compartment)]) -> Vec<char> {
     input
-        .into_iter()
+        .iter()
         .map(|data| {
             let set = data.1 .0.chars().collect::<HashSet<char>>();
             data.0 .0.chars().find(|c| set.contains(c)).unwrap()
@@ -1,28 +1,38 @@
+use crate::error::{parse_number, ParseError};
 use itertools::Itertools;
 use regex::Regex;
 use std::ops::RangeInclusive;
 
+const DAY: u8 = 4;
+
+type Assignment = (RangeInclusive<u32>, RangeInclusive<u32>);
+
 #[aoc_generator(day4)]
-pub fn generator(input: &str) -> Vec<(RangeInclusive<u32>, RangeInclusive<u32>)> {
-    let regex = Regex::new("[0-9]*").unwrap();
+pub fn generator(input: &str) -> Result<Vec<Assignment>, ParseError> {
+    let regex = Regex::new("[0-9]+").unwrap();
     input
         .lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(index, line)| {
             regex
                 .find_iter(line)
-                .map(|r#match| r#match.as_str().parse::<u32>().unwrap())
+                .map(|r#match| parse_number::<u32>(DAY, index + 1, line, r#match.as_str()))
+                .collect::<Result<Vec<u32>, ParseError>>()?
+                .into_iter()
                 .tuples::<(u32, u32)>()
                 .map(|(start, end)| RangeInclusive::new(start, end))
-                .collect_tuple::<(RangeInclusive<u32>, RangeInclusive<u32>)>()
-                .unwrap()
+                .collect_tuple::<Assignment>()
+                .ok_or_else(|| {
+                    ParseError::at(DAY, index + 1, line, line, "expected two ranges 'a-b,c-d'")
+                })
         })
         .collect()
 }
 
 #[aoc(day4, part1)]
-pub fn part1(input: &[(RangeInclusive<u32>, RangeInclusive<u32>)]) -> usize {
+pub fn part1(input: &[Assignment]) -> usize {
     input
-        .into_iter()
+        .iter()
         .filter(|(range1, range2)| {
             (range1.contains(range2.start()) && range1.contains(range2.end()))
                 || (range2.contains(range1.start()) && range2.contains(range1.end()))
@@ -31,9 +41,9 @@ pub fn part1(input: &[(RangeInclusive<u32>, RangeInclusive<u32>)]) -> usize {
 }
 
 #[aoc(day4, part2)]
-pub fn part2(input: &[(RangeInclusive<u32>, RangeInclusive<u32>)]) -> usize {
+pub fn part2(input: &[Assignment]) -> usize {
     input
-        .into_iter()
+        .iter()
         .filter(|(range1, range2)| range1.clone().any(|x| range2.contains(&x)))
         .count()
 }
@@ -48,7 +58,7 @@ const TEST_INPUT: &str = r#"2-4,6-8
 
 #[test]
 pub fn generator_test() {
-    let got = generator(TEST_INPUT);
+    let got = generator(TEST_INPUT).unwrap();
     let want = vec![
         (2..=4, 6..=8),
         (2..=3, 4..=5),
@@ -63,7 +73,7 @@ pub fn generator_test() {
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&generator(TEST_INPUT));
+    let got = part1(&generator(TEST_INPUT).unwrap());
     let want = 2;
 
     assert_eq!(got, want)
@@ -71,7 +81,14 @@ pub fn part1_test() {
 
 #[test]
 pub fn part2_test() {
-    let got = part2(&generator(TEST_INPUT));
+    let got = part2(&generator(TEST_INPUT).unwrap());
     let want = 4;
     assert_eq!(got, want)
 }
+
+#[test]
+pub fn generator_error_test() {
+    let got = generator("2-4,6-8\n2-3,4").unwrap_err();
+    assert_eq!(2, got.line);
+    assert_eq!(1, got.column);
+}
@@ -1,3 +1,7 @@
+use crate::error::{parse_number, ParseError};
+
+const DAY: u8 = 5;
+
 pub enum CraneModel {
     NineThousand,
     NineThousandAndOne,
@@ -27,17 +31,17 @@ impl Stacks {
             _ => (),
         }
 
-        match self.0.get_mut(instruction.to - 1) {
-            Some(stack) => stack.append(&mut to_move),
-            None => (),
+        if let Some(stack) = self.0.get_mut(instruction.to - 1) {
+            stack.append(&mut to_move)
         }
     }
 
     pub fn get_top_crates(&self) -> Vec<Crate> {
         let mut result = vec![];
-        self.0.iter().for_each(|stack| match stack.last() {
-            Some(c) => result.push(*c),
-            None => (),
+        self.0.iter().for_each(|stack| {
+            if let Some(c) = stack.last() {
+                result.push(*c)
+            }
         });
 
         result
@@ -69,39 +73,96 @@ impl Instruction {
 }
 
 #[aoc_generator(day5)]
-pub fn generate(input: &str) -> (Stacks, Vec<Instruction>) {
-    let split = input.split("\n\n").collect::<Vec<&str>>();
-    let (raw_stack, raw_instructions) = (split[0], split[1]);
+pub fn generate(input: &str) -> Result<(Stacks, Vec<Instruction>), ParseError> {
+    let lines = input.lines().collect::<Vec<&str>>();
+    let separator = lines
+        .iter()
+        .position(|line| line.is_empty())
+        .ok_or_else(|| {
+            ParseError::missing_line(
+                DAY,
+                lines.len() + 1,
+                "expected empty line separating stacks and instructions",
+            )
+        })?;
 
     let mut stacks = Stacks::default();
 
-    raw_stack
-        .lines()
+    lines[..separator]
+        .iter()
         .rev()
         .skip(1)
         .map(|line| line.chars().skip(1).step_by(4).collect::<Vec<char>>())
         .for_each(|row| row.iter().enumerate().for_each(|(i, c)| stacks.push(i, *c)));
 
-    let instructions = raw_instructions
-        .lines()
-        .map(|line| {
-            let raw_instruction = line.split(' ').collect::<Vec<&str>>();
-            Instruction {
-                count: raw_instruction[1].parse::<u32>().unwrap(),
-                from: raw_instruction[3].parse::<usize>().unwrap(),
-                to: raw_instruction[5].parse::<usize>().unwrap(),
-            }
-        })
-        .collect();
+    let instructions = lines
+        .iter()
+        .enumerate()
+        .skip(separator + 1)
+        .map(|(index, line)| parse_instruction(index + 1, line, stacks.0.len()))
+        .collect::<Result<Vec<Instruction>, ParseError>>()?;
 
-    (stacks, instructions)
+    Ok((stacks, instructions))
+}
+
+fn parse_instruction(
+    line_number: usize,
+    line: &str,
+    stacks_count: usize,
+) -> Result<Instruction, ParseError> {
+    let words = line.split(' ').collect::<Vec<&str>>();
+    let word = |i: usize, expected: &str| {
+        words
+            .get(i)
+            .copied()
+            .ok_or_else(|| ParseError::end_of_line(DAY, line_number, line, expected))
+    };
+    let keyword = |i: usize, keyword: &str| {
+        let expected = format!("expected '{keyword}'");
+        match word(i, &expected)? {
+            w if w == keyword => Ok(()),
+            w => Err(ParseError::at(DAY, line_number, line, w, expected)),
+        }
+    };
+    let stack_index = |i: usize| {
+        let w = word(i, "expected stack number")?;
+        match parse_number::<usize>(DAY, line_number, line, w)? {
+            n if (1..=stacks_count).contains(&n) => Ok(n),
+            _ => Err(ParseError::at(
+                DAY,
+                line_number,
+                line,
+                w,
+                format!("expected stack number between 1 and {stacks_count}"),
+            )),
+        }
+    };
+
+    keyword(0, "move")?;
+    let count = parse_number::<u32>(DAY, line_number, line, word(1, "expected crate count")?)?;
+    keyword(2, "from")?;
+    let from = stack_index(3)?;
+    keyword(4, "to")?;
+    let to = stack_index(5)?;
+
+    if let Some(trailing) = words.get(6) {
+        return Err(ParseError::at(
+            DAY,
+            line_number,
+            line,
+            trailing,
+            "unexpected trailing input",
+        ));
+    }
+
+    Ok(Instruction { count, from, to })
 }
 
 #[aoc(day5, part1)]
 pub fn part1((stack, instructions): &(Stacks, Vec<Instruction>)) -> String {
     let mut stack = stack.clone();
     instructions
-        .into_iter()
+        .iter()
         .for_each(|instruction| stack.process_instruction(instruction, CraneModel::NineThousand));
 
     stack.get_top_crates().into_iter().collect::<String>()
@@ -110,7 +171,7 @@ pub fn part1((stack, instructions): &(Stacks, Vec<Instruction>)) -> String {
 #[aoc(day5, part2)]
 pub fn part2((stack, instructions): &(Stacks, Vec<Instruction>)) -> String {
     let mut stack = stack.clone();
-    instructions.into_iter().for_each(|instruction| {
+    instructions.iter().for_each(|instruction| {
         stack.process_instruction(instruction, CraneModel::NineThousandAndOne)
     });
 
@@ -130,7 +191,7 @@ move 1 from 1 to 2"#;
 
 #[test]
 pub fn generator_test() {
-    let got = generate(TEST_INPUT);
+    let got = generate(TEST_INPUT).unwrap();
     let want = (
         Stacks(vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]),
         vec![
@@ -146,14 +207,26 @@ pub fn generator_test() {
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&generate(TEST_INPUT));
+    let got = part1(&generate(TEST_INPUT).unwrap());
     let want = "CMZ".to_string();
     assert_eq!(got, want)
 }
 
 #[test]
 pub fn part2_test() {
-    let got = part2(&generate(TEST_INPUT));
+    let got = part2(&generate(TEST_INPUT).unwrap());
     let want = "MCD".to_string();
     assert_eq!(got, want)
 }
+
+#[test]
+pub fn generator_error_test() {
+    let input = TEST_INPUT.replace("move 2 from 2 to 1", "move 2 form 2 to 1");
+    let got = generate(&input).unwrap_err();
+    assert_eq!((8, 8), (got.line, got.column));
+    assert_eq!("form", got.snippet);
+
+    let input = TEST_INPUT.replace("move 1 from 1 to 2", "move 1 from 1 to 4");
+    let got = generate(&input).unwrap_err();
+    assert_eq!((9, 18), (got.line, got.column));
+}
@@ -1,8 +1,10 @@
+use crate::error::{parse_number, ParseError};
 use std::collections::HashMap;
-use std::convert::Infallible;
 use std::iter::Peekable;
 use std::slice::Iter;
 
+const DAY: u8 = 7;
+
 type FileName = String;
 
 #[derive(Debug)]
@@ -38,42 +40,39 @@ impl<'i> FilePath<'i> {
 pub struct FileSystem(HashMap<FileName, FileDescriptor>);
 
 impl FileSystem {
-    pub fn add_folder(&mut self, path: &FilePath, name: &str) {
-        if path.is_empty() {
-            return;
-        }
-
+    pub fn add_folder(&mut self, path: &FilePath, name: &str) -> Result<(), String> {
         let mut iter = path.iter();
-        let root = iter.next().unwrap().to_string();
+        let Some(root) = iter.next() else {
+            return Ok(());
+        };
+
+        self.root_folder(root).insert_folder(&mut iter, root, name)
+    }
+
+    pub fn add_file(&mut self, path: &FilePath, name: &str, size: u32) -> Result<(), String> {
+        let mut peekable_iter = path.peek();
+        let Some(root) = peekable_iter.next() else {
+            return Ok(());
+        };
+
+        self.root_folder(root)
+            .insert_file(&mut peekable_iter, root, name, size)
+    }
 
+    fn root_folder(&mut self, root: &str) -> &mut FileDescriptor {
         self.0
-            .entry(root.clone())
+            .entry(root.to_string())
             .or_insert(FileDescriptor::Folder {
                 content: HashMap::new(),
                 name: root.to_string(),
                 size: 0,
                 parent: None,
             })
-            .insert_folder(&mut iter, root.as_str(), name);
-    }
-
-    pub fn add_file(&mut self, path: &FilePath, name: &str, size: u32) {
-        if path.is_empty() {
-            return;
-        }
-
-        let mut peekable_iter = path.peek();
-        let root = peekable_iter.next().unwrap();
-
-        match self.0.get_mut(&root.to_string()) {
-            Some(folder) => folder.insert_file(&mut peekable_iter, root, name, size),
-            None => unreachable!(),
-        }
     }
 
     pub fn sum_folder_sizes_for(&self, counter: &mut u32) {
         self.0
-            .get(&"/".to_string())
+            .get("/")
             .expect("root not found!")
             .sum_folder_sizes(counter)
     }
@@ -111,12 +110,19 @@ enum FileDescriptor {
 }
 
 impl FileDescriptor {
-    pub fn insert_folder(&mut self, path: &mut Iter<'_, &str>, parent: &str, name: &str) {
-        let FileDescriptor::Folder {content, ..} = self else { return; };
+    pub fn insert_folder(
+        &mut self,
+        path: &mut Iter<'_, &str>,
+        parent: &str,
+        name: &str,
+    ) -> Result<(), String> {
+        let FileDescriptor::Folder { content, .. } = self else {
+            return Err(format!("'{parent}' is not a folder"));
+        };
         match path.next() {
             Some(p) => content
-                .get_mut(&p.to_string())
-                .expect("no parent found")
+                .get_mut(*p)
+                .ok_or_else(|| format!("unknown folder '{p}'"))?
                 .insert_folder(path, p, name),
             None => {
                 content.insert(
@@ -128,6 +134,7 @@ impl FileDescriptor {
                         content: HashMap::new(),
                     },
                 );
+                Ok(())
             }
         }
     }
@@ -138,22 +145,20 @@ impl FileDescriptor {
         parent: &str,
         name: &str,
         file_size: u32,
-    ) {
-        let FileDescriptor::Folder {content, size, ..} = self else { return; };
+    ) -> Result<(), String> {
+        let FileDescriptor::Folder { content, size, .. } = self else {
+            return Err(format!("'{parent}' is not a folder"));
+        };
         *size += file_size;
         //dbg!(&name, size);
         let next_parent = path.next();
 
         match next_parent {
             // there's another folder in the path so we go deeper
-            Some(next_parent) => {
-                if let Some(data) = content.get_mut(&next_parent.to_string()) {
-                    data.insert_file(path, next_parent, name, file_size);
-                } else {
-                    dbg!(self);
-                    panic!("fuck");
-                }
-            }
+            Some(next_parent) => match content.get_mut(*next_parent) {
+                Some(data) => data.insert_file(path, next_parent, name, file_size),
+                None => Err(format!("unknown folder '{next_parent}'")),
+            },
             None => {
                 // next there is no path, so this folder is last one, so we put file here
                 content.insert(
@@ -164,13 +169,14 @@ impl FileDescriptor {
                         size: file_size,
                     },
                 );
+                Ok(())
             }
         }
     }
 
     pub fn sum_folder_sizes(&self, counter: &mut u32) {
-        let FileDescriptor::Folder {size, content, ..} = self else {
-            return ;
+        let FileDescriptor::Folder { size, content, .. } = self else {
+            return;
         };
 
         if *size <= 100000 {
@@ -183,7 +189,7 @@ impl FileDescriptor {
     }
 
     pub fn record_folder_size(&self, sizes: &mut Vec<u32>) {
-        let FileDescriptor::Folder {size, content, ..} = self else {
+        let FileDescriptor::Folder { size, content, .. } = self else {
             return;
         };
 
@@ -202,48 +208,62 @@ pub enum TerminalLine<'i> {
 }
 
 impl<'i> TerminalLine<'i> {
-    fn from_str(s: &'i str) -> Result<Self, Infallible> {
-        if s.starts_with("$ cd") {
-            return Ok(TerminalLine::ChangeDir(s.split(' ').last().unwrap()));
-        };
-
-        if s.starts_with("$ ls") {
-            return Ok(TerminalLine::List);
+    fn parse(line_number: usize, s: &'i str) -> Result<Self, ParseError> {
+        if let Some(command) = s.strip_prefix("$ ") {
+            return match command.split_once(' ') {
+                Some(("cd", dir)) if !dir.is_empty() => Ok(TerminalLine::ChangeDir(dir)),
+                None if command == "ls" => Ok(TerminalLine::List),
+                _ => Err(ParseError::at(
+                    DAY,
+                    line_number,
+                    s,
+                    command,
+                    "expected 'cd <dir>' or 'ls' command",
+                )),
+            };
         }
 
-        if s.starts_with("dir") {
-            return Ok(TerminalLine::Directory(s.split(' ').last().unwrap()));
+        match s.split_once(' ') {
+            Some(("dir", name)) => Ok(TerminalLine::Directory(name)),
+            Some((size, name)) => Ok(TerminalLine::File {
+                name,
+                size: parse_number::<u32>(DAY, line_number, s, size)?,
+            }),
+            None => Err(ParseError::at(
+                DAY,
+                line_number,
+                s,
+                s,
+                "expected 'dir <name>' or '<size> <name>'",
+            )),
         }
-
-        let split = s.split(' ').collect::<Vec<&str>>();
-
-        Ok(TerminalLine::File {
-            name: split[1],
-            size: split[0].parse::<u32>().unwrap(),
-        })
     }
 }
 
 #[aoc_generator(day7)]
-pub fn parse_input(input: &str) -> FileSystem {
+pub fn parse_input(input: &str) -> Result<FileSystem, ParseError> {
     let mut path = FilePath(vec![]);
     let mut file_system = FileSystem(HashMap::new());
-    input
-        .lines()
-        .for_each(|line| match TerminalLine::from_str(line) {
-            Ok(TerminalLine::ChangeDir(dir)) if dir == ".." => {
+
+    for (index, line) in input.lines().enumerate() {
+        let added = match TerminalLine::parse(index + 1, line)? {
+            TerminalLine::ChangeDir("..") => {
                 path.pop();
+                Ok(())
             }
-            Ok(TerminalLine::ChangeDir(dir)) => {
-                file_system.add_folder(&path, dir);
+            TerminalLine::ChangeDir(dir) => {
+                let added = file_system.add_folder(&path, dir);
                 path.push(dir);
+                added
             }
-            Ok(TerminalLine::File { name, size }) => {
-                file_system.add_file(&path, name, size);
-            }
-            _ => (),
-        });
-    file_system
+            TerminalLine::File { name, size } => file_system.add_file(&path, name, size),
+            _ => Ok(()),
+        };
+
+        added.map_err(|message| ParseError::at(DAY, index + 1, line, line, message))?;
+    }
+
+    Ok(file_system)
 }
 
 #[aoc(day7, part1)]
@@ -275,3 +295,56 @@ pub fn part2(input: &FileSystem) -> u32 {
         .first()
         .expect("expected at least one folder size")
 }
+
+#[cfg(test)]
+const TEST_INPUT: &str = r#"$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k"#;
+
+#[test]
+pub fn part1_test() {
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
+    assert_eq!(95437, got)
+}
+
+#[test]
+pub fn part2_test() {
+    let got = part2(&parse_input(TEST_INPUT).unwrap());
+    assert_eq!(24933642, got)
+}
+
+#[test]
+pub fn parse_input_error_test() {
+    let input = TEST_INPUT.replace("$ ls\n584 i", "$ ls\n584i");
+    let got = parse_input(&input).unwrap_err();
+    assert_eq!((15, 1), (got.line, got.column));
+
+    let input = TEST_INPUT.replace("$ cd e", "$ cd");
+    let got = parse_input(&input).unwrap_err();
+    assert_eq!((13, 3), (got.line, got.column));
+    assert_eq!("expected 'cd <dir>' or 'ls' command", got.message);
+
+    let input = TEST_INPUT.replace("$ cd ..", "$ up");
+    let got = parse_input(&input).unwrap_err();
+    assert_eq!((16, 3), (got.line, got.column));
+}
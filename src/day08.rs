@@ -1,232 +1,122 @@
+use crate::error::ParseError;
+use crate::grid::{Grid, NEIGHBORS_4};
 #[cfg(test)]
-const OFFSET: u8 = 48;
+use crate::grid::{DOWN, LEFT, RIGHT, UP};
+use glam::IVec2;
+
+const DAY: u8 = 8;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub struct Grid {
-    width: usize,
-    data: Vec<u8>,
+pub struct Forest {
+    grid: Grid<u8>,
 }
 
-// to get row: index / width
-// position on a row: index % width
-impl Grid {
-    fn is_on_edge(&self, index: usize) -> bool {
-        let index = index + 1;
-        // right side
-        index % self.width == 0
-            // left side
-            || index % self.width == 1
-            // first row
-            || index < self.width
-            // last row
-            || index > self.data.len() - self.width
+impl Forest {
+    fn is_on_edge(&self, pos: IVec2) -> bool {
+        pos.x == 0
+            || pos.y == 0
+            || pos.x == self.grid.width() as i32 - 1
+            || pos.y == self.grid.height() as i32 - 1
     }
 
     pub fn count_visible(&self) -> u32 {
-        let mut counter = 0;
-
-        for (index, tree) in self.data.iter().enumerate() {
-            if self.is_on_edge(index) {
-                counter += 1;
-                continue;
-            }
-
-            if self.is_visible_from_left(index, *tree)
-                || self.is_visible_from_top(index, *tree)
-                || self.is_visible_from_right(index, *tree)
-                || self.is_visible_from_bottom(index, *tree)
-            {
-                counter += 1;
-            }
-        }
-
-        counter
+        self.grid
+            .positions()
+            .filter(|pos| {
+                self.is_on_edge(*pos)
+                    || NEIGHBORS_4
+                        .iter()
+                        .any(|direction| self.is_visible_from(*pos, *direction))
+            })
+            .count() as u32
     }
 
     pub fn count_scenic_score_for_trees(&self) -> Vec<u32> {
-        self.data
-            .iter()
-            .enumerate()
-            .map(|(index, value)| {
-                self.scenic_score_left(index, *value)
-                    * self.scenic_score_top(index, *value)
-                    * self.scenic_score_right(index, *value)
-                    * self.scenic_score_bottom(index, *value)
+        self.grid
+            .positions()
+            .map(|pos| {
+                NEIGHBORS_4
+                    .iter()
+                    .map(|direction| self.scenic_score(pos, *direction))
+                    .product()
             })
             .collect::<Vec<u32>>()
     }
 
-    // 8, 1 | 23, 9
-    fn is_visible_from_left(&self, index: usize, value: u8) -> bool {
-        // need to resolve start of the row and then take all until the index
-        let skip_to = (index / self.width) * self.width; // ((8 / 5) * 5)= (1 * 5) = 5 | ((23 / 5) * 5) = (4 * 5) = 20
-        let to_take = self.width - (self.width - (index % self.width)); // 5 - (5 - (8 % 5)) = 5 - (5 - 3) =  5 - 2 = 3 | 5 - (5 - ( 23 % 5)) = 5 - ( 5 - 3 )) = 5 - 2 = 3
-        !self
-            .data
-            .iter() // 3037325512653323354935390
-            .skip(skip_to) // 30373 25512653323354935390 | 30373255126533233549 35390
-            .take(to_take) // 30373 255 12.65332.33549.35390 | 30373255126533233549 353 90
-            .find(|v| **v >= value) // 2 >= 1 | 3 >= 9, 5 >= 9, 3 >= 9
-            .is_some()
+    fn height(&self, pos: IVec2) -> u8 {
+        *self.grid.get(pos).expect("position outside of the forest")
     }
 
-    fn scenic_score_left(&self, index: usize, value: u8) -> u32 {
-        if index % self.width == 1 {
-            return 0;
-        }
-
-        let skip_to = (index / self.width) * self.width;
-        let to_take = self.width - (self.width - (index % self.width));
-
-        let mut counter = 0;
-
-        for tree in self
-            .data
-            .iter() // 3037325512653323354935390
-            .skip(skip_to) // 30373 25512653323354935390 | 30373255126533233549 35390
-            .take(to_take)
-        // 30373 255 12.65332.33549.35390 | 30373255126533233549 353 90
-        {
-            if *tree >= value {
-                break;
-            }
-
-            counter += 1;
-        }
-        counter
-    }
-
-    // 16, 3 | 23, 9
-    fn is_visible_from_top(&self, index: usize, value: u8) -> bool {
-        let to_skip = index % self.width;
-
-        !self
-            .data
-            .iter() // 30373.25512.65332.33549.35390
-            .take(index - 1) // 30373.25512.65332.3 3549.35390
-            .skip(to_skip) // 0373.25512.65332.3
-            .step_by(self.width) // 0373.25512.65332.3
-            .find(|v| **v >= value)
-            .is_some()
+    fn is_visible_from(&self, pos: IVec2, direction: IVec2) -> bool {
+        let height = self.height(pos);
+        self.grid
+            .ray(pos, direction)
+            .all(|(_, tree)| *tree < height)
     }
 
-    fn scenic_score_top(&self, index: usize, value: u8) -> u32 {
-        if index < self.width {
-            return 0;
-        }
-
-        let to_skip = index % self.width;
+    // trees until the edge or the first tree at least as tall, that one included
+    fn scenic_score(&self, pos: IVec2, direction: IVec2) -> u32 {
+        let height = self.height(pos);
         let mut counter = 0;
 
-        for tree in self
-            .data
-            .iter() // 30373.25512.65332.33549.35390
-            .take(index - 1) // 30373.25512.65332.3 3549.35390
-            .skip(to_skip) // 0373.25512.65332.3
-            .step_by(self.width)
-        {
-            if *tree >= value {
-                break;
-            }
-
+        for (_, tree) in self.grid.ray(pos, direction) {
             counter += 1;
-        }
-        counter
-    }
-
-    // 16, 3 | 7, 5
-    fn is_visible_from_right(&self, index: usize, value: u8) -> bool {
-        let to_take = self.width - (index % self.width) - 1; // (5 - (16 % 5)) - 1 = (5 - 1) - 1 = 4 - 1 = 3 | (5 - (7 % 5)) -1 = (5 - 2) - 1 = 3 - 1 = 2
-        !self
-            .data
-            .iter() // 3037325512653323 3 549 35390 | 30373.25 5 12. 65332.33549.35390
-            .skip(index + 1)
-            .take(to_take) // 549 | 12
-            .find(|v| **v >= value) // 5 >= 3, 4 >= 3, 9 >= 3 | 1 >= 5, 2 >= 5
-            .is_some()
-    }
-
-    fn scenic_score_right(&self, index: usize, value: u8) -> u32 {
-        if index % self.width == 0 {
-            return 0;
-        }
-
-        let to_take = self.width - (index % self.width) - 1; // (5 - (16 % 5)) - 1 = (5 - 1) - 1 = 4 - 1 = 3 | (5 - (7 % 5)) -1 = (5 - 2) - 1 = 3 - 1 = 2
-        let mut counter = 0;
 
-        for tree in self
-            .data
-            .iter() // 3037325512653323 3 549 35390 | 30373.25 5 12. 65332.33549.35390
-            .skip(index + 1)
-            .take(to_take)
-        // 549 | 12
-        {
-            if *tree >= value {
+            if *tree >= height {
                 break;
             }
-
-            counter += 1;
         }
 
         counter
     }
+}
 
-    // 3, 7 | 10, 6
-    fn is_visible_from_bottom(&self, index: usize, value: u8) -> bool {
-        let to_take = self.data.len() - index;
-
-        !self
-            .data
-            .iter() // 30373.25512.65332.33549.35390
-            .skip(index) //  303 73.25512.65332.33549.35390
-            .take(to_take) // 73.25512.65332.33549.35390
-            .step_by(self.width) // 73.255 12.653 32.335 49.353 90
-            .skip(1) // 12.653 32.335 49.353 90
-            .find(|v| **v >= value)
-            .is_some()
-    }
-
-    fn scenic_score_bottom(&self, index: usize, value: u8) -> u32 {
-        if index > self.data.len() - self.width {
-            return 0;
+#[aoc_generator(day8)]
+pub fn parse_input(input: &str) -> Result<Forest, ParseError> {
+    let width = input
+        .lines()
+        .next()
+        .map(|line| line.len())
+        .filter(|width| *width > 0)
+        .ok_or_else(|| ParseError::missing_line(DAY, 1, "expected at least one row of trees"))?;
+
+    let mut data = Vec::with_capacity(input.len());
+    for (index, line) in input.lines().enumerate() {
+        if line.len() != width {
+            return Err(ParseError::at(
+                DAY,
+                index + 1,
+                line,
+                line,
+                format!("expected row of {width} trees"),
+            ));
         }
 
-        let to_take = self.data.len() - index;
-        let mut counter = 0;
-
-        for tree in self
-            .data
-            .iter() // 30373.25512.65332.33549.35390
-            .skip(index) //  303 73.25512.65332.33549.35390
-            .take(to_take) // 73.25512.65332.33549.35390
-            .step_by(self.width) // 73.255 12.653 32.335 49.353 90
-            .skip(1)
-        {
-            if *tree >= value {
-                break;
-            }
-
-            counter += 1
+        if let Some((position, c)) = line.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+            return Err(ParseError::at(
+                DAY,
+                index + 1,
+                line,
+                &line[position..position + c.len_utf8()],
+                "expected tree height digit",
+            ));
         }
 
-        counter
+        data.extend(line.bytes().map(|b| b - b'0'));
     }
-}
 
-#[aoc_generator(day8)]
-pub fn parse_input(input: &str) -> Grid {
-    let width = input.lines().next().unwrap().chars().count();
-    let data = input.replace("\n", "").into_bytes();
-    Grid { width, data }
+    Ok(Forest {
+        grid: Grid::from_vec(width, data),
+    })
 }
 
 #[aoc(day8, part1)]
-pub fn part1(input: &Grid) -> u32 {
+pub fn part1(input: &Forest) -> u32 {
     input.count_visible()
 }
 
 #[aoc(day8, part2)]
-pub fn part2(input: &Grid) -> u32 {
+pub fn part2(input: &Forest) -> u32 {
     let mut scores = input.count_scenic_score_for_trees();
     scores.sort();
     *scores.last().unwrap()
@@ -241,10 +131,9 @@ const TEST_INPUT: &str = r#"30373
 
 #[test]
 pub fn parse_input_test() {
-    let got = parse_input("123\n456");
-    let want = Grid {
-        width: 3,
-        data: vec![49, 50, 51, 52, 53, 54],
+    let got = parse_input("123\n456").unwrap();
+    let want = Forest {
+        grid: Grid::from_vec(3, vec![1, 2, 3, 4, 5, 6]),
     };
 
     assert_eq!(want, got)
@@ -252,47 +141,46 @@ pub fn parse_input_test() {
 
 #[test]
 pub fn part1_test() {
-    let grid = parse_input(TEST_INPUT);
-    let got = grid.count_visible();
+    let forest = parse_input(TEST_INPUT).unwrap();
+    let got = forest.count_visible();
     assert_eq!(21, got)
 }
 
 #[test]
 pub fn part2_test() {
-    let grid = parse_input(TEST_INPUT);
-    let got = part2(&grid);
+    let forest = parse_input(TEST_INPUT).unwrap();
+    let got = part2(&forest);
     assert_eq!(8, got)
 }
 
 #[test]
 pub fn is_on_edge_test() {
-    let grid = parse_input(TEST_INPUT);
-    let count = grid
-        .data
-        .iter()
-        .enumerate()
-        .filter(|(index, _)| grid.is_on_edge(*index))
+    let forest = parse_input(TEST_INPUT).unwrap();
+    let count = forest
+        .grid
+        .positions()
+        .filter(|pos| forest.is_on_edge(*pos))
         .count();
     let want = 16;
 
     assert_eq!(want, count);
-    assert_eq!(true, grid.is_on_edge(4));
-    assert_eq!(false, grid.is_on_edge(6));
-    assert_eq!(false, grid.is_on_edge(7));
+    assert_eq!(true, forest.is_on_edge(IVec2::new(4, 0)));
+    assert_eq!(false, forest.is_on_edge(IVec2::new(1, 1)));
+    assert_eq!(false, forest.is_on_edge(IVec2::new(2, 1)));
 }
 
 #[test]
 pub fn is_visible_from_right_test() {
-    let grid = parse_input(TEST_INPUT);
-    assert_eq!(true, grid.is_visible_from_right(7, 5 + OFFSET));
-    assert_eq!(false, grid.is_visible_from_right(16, 3 + OFFSET));
+    let forest = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(true, forest.is_visible_from(IVec2::new(2, 1), RIGHT));
+    assert_eq!(false, forest.is_visible_from(IVec2::new(1, 3), RIGHT));
 }
 
 #[test]
 pub fn is_visible_from_left_test() {
-    let grid = parse_input(TEST_INPUT);
-    assert_eq!(false, grid.is_visible_from_left(8, 1 + OFFSET));
-    assert_eq!(true, grid.is_visible_from_left(23, 9 + OFFSET));
+    let forest = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(false, forest.is_visible_from(IVec2::new(3, 1), LEFT));
+    assert_eq!(true, forest.is_visible_from(IVec2::new(3, 4), LEFT));
 }
 
 // 30373
@@ -303,14 +191,33 @@ pub fn is_visible_from_left_test() {
 
 #[test]
 pub fn is_visible_from_top_test() {
-    let grid = parse_input(TEST_INPUT);
-    assert_eq!(false, grid.is_visible_from_top(16, 3 + OFFSET));
-    assert_eq!(true, grid.is_visible_from_top(23, 9 + OFFSET));
+    let forest = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(false, forest.is_visible_from(IVec2::new(1, 3), UP));
+    assert_eq!(true, forest.is_visible_from(IVec2::new(3, 4), UP));
 }
 
 #[test]
 pub fn is_visible_from_bottom_test() {
-    let grid = parse_input(TEST_INPUT);
-    assert_eq!(false, grid.is_visible_from_bottom(3, 7 + OFFSET));
-    assert_eq!(true, grid.is_visible_from_bottom(10, 6 + OFFSET));
+    let forest = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(false, forest.is_visible_from(IVec2::new(3, 0), DOWN));
+    assert_eq!(true, forest.is_visible_from(IVec2::new(0, 2), DOWN));
+}
+
+#[test]
+pub fn scenic_score_test() {
+    let forest = parse_input(TEST_INPUT).unwrap();
+    let pos = IVec2::new(2, 3);
+    assert_eq!(2, forest.scenic_score(pos, UP));
+    assert_eq!(2, forest.scenic_score(pos, LEFT));
+    assert_eq!(1, forest.scenic_score(pos, DOWN));
+    assert_eq!(2, forest.scenic_score(pos, RIGHT));
+}
+
+#[test]
+pub fn parse_input_error_test() {
+    let got = parse_input("123\n45").unwrap_err();
+    assert_eq!((2, 1), (got.line, got.column));
+
+    let got = parse_input("123\n4x6").unwrap_err();
+    assert_eq!((2, 2), (got.line, got.column));
 }
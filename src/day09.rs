@@ -1,6 +1,9 @@
+use crate::error::{parse_number, ParseError};
 use glam::IVec2;
 use std::collections::HashSet;
 
+const DAY: u8 = 9;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Instruction {
     Left(u8),
@@ -9,14 +12,31 @@ pub enum Instruction {
     Down(u8),
 }
 
-impl From<(&str, &str)> for Instruction {
-    fn from((instruction, count): (&str, &str)) -> Self {
-        match instruction {
-            "L" => Instruction::Left(count.parse::<u8>().unwrap()),
-            "U" => Instruction::Up(count.parse::<u8>().unwrap()),
-            "R" => Instruction::Right(count.parse::<u8>().unwrap()),
-            "D" => Instruction::Down(count.parse::<u8>().unwrap()),
-            _ => unreachable!("unexpected char in input: '{instruction}'"),
+impl Instruction {
+    fn parse(line_number: usize, line: &str) -> Result<Self, ParseError> {
+        let Some((direction, count)) = line.split_once(' ') else {
+            return Err(ParseError::at(
+                DAY,
+                line_number,
+                line,
+                line,
+                "expected '<direction> <steps>'",
+            ));
+        };
+        let count = parse_number::<u8>(DAY, line_number, line, count)?;
+
+        match direction {
+            "L" => Ok(Instruction::Left(count)),
+            "U" => Ok(Instruction::Up(count)),
+            "R" => Ok(Instruction::Right(count)),
+            "D" => Ok(Instruction::Down(count)),
+            _ => Err(ParseError::at(
+                DAY,
+                line_number,
+                line,
+                direction,
+                "expected direction 'L', 'U', 'R' or 'D'",
+            )),
         }
     }
 }
@@ -166,12 +186,13 @@ fn is_touching(a: IVec2, b: IVec2) -> bool {
 }
 
 #[aoc_generator(day9)]
-pub fn parse_input(input: &str) -> Vec<Instruction> {
+pub fn parse_input(input: &str) -> Result<Vec<Instruction>, ParseError> {
     input
         .lines()
-        .filter_map(|line| line.split_once(' '))
-        .map(Instruction::from)
-        .collect::<Vec<Instruction>>()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| Instruction::parse(index + 1, line))
+        .collect::<Result<Vec<Instruction>, ParseError>>()
 }
 
 #[aoc(day9, part1)]
@@ -180,7 +201,7 @@ pub fn part1(input: &[Instruction]) -> usize {
     input
         .iter()
         .for_each(|instruction| snake.move_snake(instruction));
-    snake.tail[0].visited.iter().count()
+    snake.tail[0].visited.len()
 }
 
 #[aoc(day9, part2)]
@@ -190,7 +211,7 @@ pub fn part2(input: &[Instruction]) -> usize {
         .iter()
         .for_each(|instruction| snake.move_snake(instruction));
 
-    snake.tail.last().unwrap().visited.iter().count()
+    snake.tail.last().unwrap().visited.len()
 }
 
 #[cfg(test)]
@@ -215,7 +236,7 @@ U 20"#;
 
 #[test]
 fn parse_input_test() {
-    let got = parse_input(TEST_INPUT);
+    let got = parse_input(TEST_INPUT).unwrap();
     let want = vec![
         Instruction::Right(4),
         Instruction::Up(4),
@@ -229,15 +250,27 @@ fn parse_input_test() {
     assert_eq!(want, got)
 }
 
+#[test]
+fn parse_input_error_test() {
+    let got = parse_input("R 4\nX 4").unwrap_err();
+    assert_eq!((2, 1), (got.line, got.column));
+
+    let got = parse_input("R 4\nU -4").unwrap_err();
+    assert_eq!((2, 3), (got.line, got.column));
+
+    let got = parse_input("R4").unwrap_err();
+    assert_eq!("expected '<direction> <steps>'", got.message);
+}
+
 #[test]
 pub fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(13, got)
 }
 
 #[test]
 pub fn part2_test() {
-    let got = part2(&parse_input(TEST_INPUT_2));
+    let got = part2(&parse_input(TEST_INPUT_2).unwrap());
     assert_eq!(36, got)
 }
 
@@ -1,4 +1,590 @@
-#[derive(Debug, PartialEq, Eq)]
+use crate::error::{column_of, ParseError};
+use std::fmt;
+use std::io::{self, Write};
+
+const DAY: u8 = 10;
+
+/// Index of the `x` register in the standard instruction set.
+pub const X: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Register,
+    Immediate,
+    /// register or immediate
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(usize),
+    Immediate(i32),
+}
+
+impl Operand {
+    pub fn value(&self, registers: &[i32]) -> i32 {
+        match self {
+            Operand::Register(index) => registers[*index],
+            Operand::Immediate(value) => *value,
+        }
+    }
+
+    fn register(&self) -> usize {
+        match self {
+            Operand::Register(index) => *index,
+            Operand::Immediate(_) => panic!("operand is not a register"),
+        }
+    }
+
+    fn target(&self, registers: &[i32]) -> usize {
+        // negative targets can never be reached, they end the program like any target past it
+        usize::try_from(self.value(registers)).unwrap_or(usize::MAX)
+    }
+
+    fn matches(&self, kind: OperandKind) -> bool {
+        matches!(
+            (self, kind),
+            (_, OperandKind::Value)
+                | (Operand::Register(_), OperandKind::Register)
+                | (Operand::Immediate(_), OperandKind::Immediate)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    Jump(usize),
+}
+
+pub type Effect = fn(&mut [i32], &[Operand]) -> Flow;
+
+/// An entry of the instruction table. The effect is applied once all of its cycles passed.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionDef {
+    pub mnemonic: &'static str,
+    pub operands: &'static [OperandKind],
+    pub cycles: usize,
+    pub effect: Effect,
+}
+
+pub const NOOP: InstructionDef = InstructionDef {
+    mnemonic: "noop",
+    operands: &[],
+    cycles: 1,
+    effect: |_, _| Flow::Next,
+};
+
+pub const ADDX: InstructionDef = InstructionDef {
+    mnemonic: "addx",
+    operands: &[OperandKind::Value],
+    cycles: 2,
+    effect: |registers, operands| {
+        registers[X] = registers[X].wrapping_add(operands[0].value(registers));
+        Flow::Next
+    },
+};
+
+const STANDARD: [InstructionDef; 8] = [
+    NOOP,
+    ADDX,
+    InstructionDef {
+        mnemonic: "set",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 1,
+        effect: |registers, operands| {
+            registers[operands[0].register()] = operands[1].value(registers);
+            Flow::Next
+        },
+    },
+    InstructionDef {
+        mnemonic: "add",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            let value = operands[1].value(registers);
+            let register = &mut registers[operands[0].register()];
+            *register = register.wrapping_add(value);
+            Flow::Next
+        },
+    },
+    InstructionDef {
+        mnemonic: "mul",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            let value = operands[1].value(registers);
+            let register = &mut registers[operands[0].register()];
+            *register = register.wrapping_mul(value);
+            Flow::Next
+        },
+    },
+    InstructionDef {
+        mnemonic: "jmp",
+        operands: &[OperandKind::Value],
+        cycles: 1,
+        effect: |registers, operands| Flow::Jump(operands[0].target(registers)),
+    },
+    InstructionDef {
+        mnemonic: "jz",
+        operands: &[OperandKind::Value, OperandKind::Value],
+        cycles: 1,
+        effect: |registers, operands| match operands[0].value(registers) {
+            0 => Flow::Jump(operands[1].target(registers)),
+            _ => Flow::Next,
+        },
+    },
+    InstructionDef {
+        mnemonic: "jnz",
+        operands: &[OperandKind::Value, OperandKind::Value],
+        cycles: 1,
+        effect: |registers, operands| match operands[0].value(registers) {
+            0 => Flow::Next,
+            _ => Flow::Jump(operands[1].target(registers)),
+        },
+    },
+];
+
+/// Registers with their initial values and the instruction table of a machine.
+#[derive(Debug, Clone, Default)]
+pub struct InstructionSet {
+    registers: Vec<(&'static str, i32)>,
+    instructions: Vec<InstructionDef>,
+}
+
+impl InstructionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `x` starting at 1 and `a` to `d` starting at 0, `noop` and `addx` of the puzzle,
+    /// `set`, `add` and `mul` on any register and the jumps `jmp`, `jz` and `jnz` to absolute
+    /// instruction indices. Arithmetic wraps around like on a 32-bit machine.
+    pub fn standard() -> Self {
+        let mut set = Self::new()
+            .with_register("x", 1)
+            .with_register("a", 0)
+            .with_register("b", 0)
+            .with_register("c", 0)
+            .with_register("d", 0);
+        set.instructions = STANDARD.to_vec();
+        set
+    }
+
+    /// Only register `x` starting at 1 with `noop` and `addx` taking a number.
+    pub fn puzzle() -> Self {
+        Self::new()
+            .with_register("x", 1)
+            .with_instruction(NOOP)
+            .with_instruction(InstructionDef {
+                operands: &[OperandKind::Immediate],
+                ..ADDX
+            })
+    }
+
+    pub fn with_register(mut self, name: &'static str, initial: i32) -> Self {
+        self.registers.push((name, initial));
+        self
+    }
+
+    /// Adds the instruction to the table, replacing any with the same mnemonic.
+    pub fn with_instruction(mut self, definition: InstructionDef) -> Self {
+        match self.opcode(definition.mnemonic) {
+            Some(opcode) => self.instructions[opcode] = definition,
+            None => self.instructions.push(definition),
+        }
+        self
+    }
+
+    pub fn register(&self, name: &str) -> Option<usize> {
+        self.registers.iter().position(|(n, _)| *n == name)
+    }
+
+    pub fn register_name(&self, index: usize) -> &'static str {
+        self.registers[index].0
+    }
+
+    pub fn opcode(&self, mnemonic: &str) -> Option<usize> {
+        self.instructions
+            .iter()
+            .position(|definition| definition.mnemonic == mnemonic)
+    }
+
+    pub fn definition(&self, opcode: usize) -> &InstructionDef {
+        &self.instructions[opcode]
+    }
+
+    /// Builds an instruction after checking the operands against the table.
+    pub fn instruction(&self, mnemonic: &str, operands: &[Operand]) -> Result<Instruction, String> {
+        let opcode = self
+            .opcode(mnemonic)
+            .ok_or_else(|| format!("unknown instruction '{mnemonic}'"))?;
+        let kinds = self.instructions[opcode].operands;
+
+        if kinds.len() != operands.len() {
+            return Err(format!(
+                "'{mnemonic}' expects {} operands, got {}",
+                kinds.len(),
+                operands.len()
+            ));
+        }
+
+        for (index, (operand, kind)) in operands.iter().zip(kinds).enumerate() {
+            if !operand.matches(*kind) {
+                return Err(format!(
+                    "operand {} of '{mnemonic}' must be {kind:?}",
+                    index + 1
+                ));
+            }
+            if let Operand::Register(register) = operand {
+                if *register >= self.registers.len() {
+                    return Err(format!("unknown register {register}"));
+                }
+            }
+        }
+
+        Ok(Instruction {
+            opcode,
+            operands: operands.to_vec(),
+        })
+    }
+
+    /// Mnemonic followed by the operands, registers by their names.
+    pub fn format(&self, instruction: &Instruction) -> String {
+        let mut text = self.definition(instruction.opcode).mnemonic.to_string();
+        for operand in &instruction.operands {
+            match operand {
+                Operand::Register(index) => text += &format!(" {}", self.register_name(*index)),
+                Operand::Immediate(value) => text += &format!(" {value}"),
+            }
+        }
+
+        text
+    }
+}
+
+/// Instruction resolved against an instruction set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    opcode: usize,
+    operands: Vec<Operand>,
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> usize {
+        self.opcode
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands
+    }
+}
+
+pub struct Vm {
+    set: InstructionSet,
+    registers: Vec<i32>,
+    pc: usize,
+    cycle: usize,
+    // cycles already spent on the instruction at the program counter
+    progress: usize,
+}
+
+impl Vm {
+    pub fn new(set: InstructionSet) -> Self {
+        Vm {
+            registers: set.registers.iter().map(|(_, initial)| *initial).collect(),
+            set,
+            pc: 0,
+            cycle: 0,
+            progress: 0,
+        }
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet {
+        &self.set
+    }
+
+    pub fn registers(&self) -> &[i32] {
+        &self.registers
+    }
+
+    pub fn register(&self, name: &str) -> Option<i32> {
+        self.set.register(name).map(|index| self.registers[index])
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Executes the instruction and moves the program counter. `on_cycle` gets the number of
+    /// every cycle the instruction takes, counted from 1, with the registers during that cycle.
+    pub fn execute(&mut self, instruction: &Instruction, mut on_cycle: impl FnMut(usize, &[i32])) {
+        let definition = self.set.definition(instruction.opcode);
+        for _ in 0..definition.cycles {
+            self.cycle += 1;
+            on_cycle(self.cycle, &self.registers);
+        }
+
+        self.pc = match (definition.effect)(&mut self.registers, &instruction.operands) {
+            Flow::Next => self.pc + 1,
+            Flow::Jump(target) => target,
+        };
+    }
+
+    /// Advances a single cycle of the instruction at the program counter, its effect lands at the
+    /// end of its last cycle. Returns `false` once the program counter points past the program.
+    pub fn tick(&mut self, program: &[Instruction], on_cycle: impl FnOnce(usize, &[i32])) -> bool {
+        let Some(instruction) = program.get(self.pc) else {
+            return false;
+        };
+
+        self.cycle += 1;
+        self.progress += 1;
+        on_cycle(self.cycle, &self.registers);
+
+        let definition = self.set.definition(instruction.opcode);
+        if self.progress >= definition.cycles {
+            self.progress = 0;
+            self.pc = match (definition.effect)(&mut self.registers, &instruction.operands) {
+                Flow::Next => self.pc + 1,
+                Flow::Jump(target) => target,
+            };
+        }
+
+        true
+    }
+
+    /// Executes the rest of the instruction at the program counter.
+    /// Returns `false` once the program counter points past the program.
+    pub fn step(
+        &mut self,
+        program: &[Instruction],
+        mut on_cycle: impl FnMut(usize, &[i32]),
+    ) -> bool {
+        if !self.tick(program, &mut on_cycle) {
+            return false;
+        }
+        while self.progress > 0 {
+            self.tick(program, &mut on_cycle);
+        }
+
+        true
+    }
+
+    pub fn run(&mut self, program: &[Instruction], mut on_cycle: impl FnMut(usize, &[i32])) {
+        while self.step(program, &mut on_cycle) {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// stops right before the cycle starts
+    Cycle(usize),
+    /// stops right before the instruction at this index starts
+    Instruction(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// stops after a cycle which changed the register
+    Changed(usize),
+    /// stops after a cycle which set the register to the value
+    Equals(usize, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(Breakpoint),
+    Watchpoint(Watchpoint),
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    JsonLines,
+}
+
+/// One executed cycle. `before` is `x` when the instruction started, `during` its value during the
+/// cycle and `after` the value once the cycle ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: usize,
+    pub pc: usize,
+    pub instruction: String,
+    pub before: i32,
+    pub during: i32,
+    pub after: i32,
+}
+
+impl TraceEntry {
+    pub fn write(&self, format: TraceFormat, mut out: impl Write) -> io::Result<()> {
+        match format {
+            TraceFormat::Text => writeln!(
+                out,
+                "cycle {:>4}  pc {:>3}  {:<10}  x {} -> {}  during {}",
+                self.cycle, self.pc, self.instruction, self.before, self.after, self.during
+            ),
+            TraceFormat::JsonLines => writeln!(
+                out,
+                r#"{{"cycle":{},"pc":{},"instruction":{:?},"x_before":{},"x_during":{},"x_after":{}}}"#,
+                self.cycle, self.pc, self.instruction, self.before, self.during, self.after
+            ),
+        }
+    }
+}
+
+/// Runs a program cycle by cycle, pausing on breakpoints and watchpoints.
+pub struct Debugger<'p> {
+    vm: Vm,
+    program: &'p [Instruction],
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    tracer: Option<(TraceFormat, Box<dyn Write + 'p>)>,
+    x_before: i32,
+    // the breakpoint we are paused on was already reported
+    reported: bool,
+}
+
+impl<'p> Debugger<'p> {
+    pub fn new(set: InstructionSet, program: &'p [Instruction]) -> Self {
+        let vm = Vm::new(set);
+        Debugger {
+            x_before: vm.registers.get(X).copied().unwrap_or_default(),
+            vm,
+            program,
+            breakpoints: vec![],
+            watchpoints: vec![],
+            tracer: None,
+            reported: false,
+        }
+    }
+
+    pub fn with_trace(mut self, format: TraceFormat, out: impl Write + 'p) -> Self {
+        self.tracer = Some((format, Box::new(out)));
+        self
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> Result<(), String> {
+        let (Watchpoint::Changed(register) | Watchpoint::Equals(register, _)) = watchpoint;
+        if register >= self.vm.registers.len() {
+            return Err(format!("unknown register {register}"));
+        }
+
+        self.watchpoints.push(watchpoint);
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    /// Runs a single cycle and returns what it did, `None` once the program finished.
+    pub fn step_cycle(&mut self) -> io::Result<Option<TraceEntry>> {
+        let Some(instruction) = self.program.get(self.vm.pc) else {
+            return Ok(None);
+        };
+
+        let x = |registers: &[i32]| registers.get(X).copied().unwrap_or_default();
+        if self.vm.progress == 0 {
+            self.x_before = x(&self.vm.registers);
+        }
+
+        let mut entry = TraceEntry {
+            cycle: 0,
+            pc: self.vm.pc,
+            instruction: self.vm.set.format(instruction),
+            before: self.x_before,
+            during: 0,
+            after: 0,
+        };
+        self.vm.tick(self.program, |cycle, registers| {
+            entry.cycle = cycle;
+            entry.during = x(registers);
+        });
+        entry.after = x(&self.vm.registers);
+        self.reported = false;
+
+        if let Some((format, out)) = &mut self.tracer {
+            entry.write(*format, out)?;
+        }
+
+        Ok(Some(entry))
+    }
+
+    /// Runs the rest of the current instruction.
+    /// Returns `false` if the program had already finished.
+    pub fn step_instruction(&mut self) -> io::Result<bool> {
+        if self.step_cycle()?.is_none() {
+            return Ok(false);
+        }
+        while self.vm.progress > 0 {
+            self.step_cycle()?;
+        }
+
+        Ok(true)
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit or the program finishes.
+    pub fn resume(&mut self) -> io::Result<Stop> {
+        loop {
+            if !self.reported {
+                if let Some(breakpoint) = self.breakpoint_hit() {
+                    self.reported = true;
+                    return Ok(Stop::Breakpoint(breakpoint));
+                }
+            }
+
+            let before = self.vm.registers.clone();
+            if self.step_cycle()?.is_none() {
+                return Ok(Stop::Finished);
+            }
+
+            let registers = &self.vm.registers;
+            let watchpoint = self
+                .watchpoints
+                .iter()
+                .find(|watchpoint| match **watchpoint {
+                    Watchpoint::Changed(register) => before[register] != registers[register],
+                    Watchpoint::Equals(register, value) => {
+                        before[register] != value && registers[register] == value
+                    }
+                });
+            if let Some(watchpoint) = watchpoint {
+                return Ok(Stop::Watchpoint(*watchpoint));
+            }
+        }
+    }
+
+    fn breakpoint_hit(&self) -> Option<Breakpoint> {
+        if self.vm.pc >= self.program.len() {
+            return None;
+        }
+
+        self.breakpoints
+            .iter()
+            .find(|breakpoint| match **breakpoint {
+                Breakpoint::Cycle(cycle) => self.vm.cycle + 1 == cycle,
+                Breakpoint::Instruction(index) => self.vm.progress == 0 && self.vm.pc == index,
+            })
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operation {
     Noop,
     Add(i32),
@@ -6,109 +592,547 @@ pub enum Operation {
 
 impl Operation {
     pub fn cycles_to_process(&self) -> u8 {
+        self.definition().cycles as u8
+    }
+
+    fn definition(&self) -> &'static InstructionDef {
         match self {
-            Operation::Noop => 1,
-            Operation::Add(_) => 2,
+            Operation::Noop => &NOOP,
+            Operation::Add(_) => &ADDX,
         }
     }
 }
 
-pub struct CPU<const N: usize> {
-    cycle: usize,
-    register_x: i32,
-    history: [i32; N],
+/// Only valid for the standard and puzzle instruction sets, where `noop` and `addx` are the first
+/// two opcodes.
+impl From<&Operation> for Instruction {
+    fn from(operation: &Operation) -> Self {
+        match operation {
+            Operation::Noop => Instruction {
+                opcode: 0,
+                operands: vec![],
+            },
+            Operation::Add(value) => Instruction {
+                opcode: 1,
+                operands: vec![Operand::Immediate(*value)],
+            },
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Noop => write!(f, "{}", NOOP.mnemonic),
+            Operation::Add(value) => write!(f, "{} {value}", ADDX.mnemonic),
+        }
+    }
 }
 
-impl<const N: usize> CPU<N> {
+pub struct CPU {
+    vm: Vm,
+    history: Vec<i32>,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CPU {
     pub fn new() -> Self {
         CPU {
-            cycle: 0,
-            register_x: 1,
-            history: [0; N],
+            vm: Vm::new(InstructionSet::standard()),
+            history: vec![],
         }
     }
 
-    pub fn process_operation(&mut self, operation: &Operation) {
-        match operation {
-            Operation::Noop => self.step(),
-            Operation::Add(x) => {
-                for _ in 0..operation.cycles_to_process() {
-                    self.step()
-                }
+    /// Value of `x` during every cycle so far.
+    pub fn history(&self) -> &[i32] {
+        &self.history
+    }
 
-                self.register_x += x;
-            }
-        }
+    pub fn register_x(&self) -> i32 {
+        self.vm.registers()[X]
     }
 
-    pub fn step(&mut self) {
-        self.history[self.cycle] = self.register_x;
-        self.cycle += 1;
+    pub fn process_operation(&mut self, operation: &Operation) {
+        let history = &mut self.history;
+        self.vm
+            .execute(&Instruction::from(operation), |_, registers| {
+                history.push(registers[X])
+            });
     }
 
+    // cycles the program didn't reach, and cycle 0 before the first one, have no signal
     pub fn get_signal_strenght(&self, indices: &[usize]) -> Vec<i32> {
         let mut results: Vec<i32> = Vec::new();
         for index in indices {
-            results.push(self.history[index - 1] * *index as i32)
+            let x = index
+                .checked_sub(1)
+                .and_then(|i| self.history.get(i))
+                .copied()
+                .unwrap_or(0);
+            results.push(x * *index as i32)
         }
 
         results
     }
 }
 
-struct CRT {
+#[allow(clippy::upper_case_acronyms)]
+pub struct CRT {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+    // position within the last frame
     index: usize,
-    drawing: [char; 240],
+    frames: Vec<Vec<bool>>,
+}
+
+impl Default for CRT {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CRT {
+    /// 40x6 screen with a sprite three pixels wide.
     pub fn new() -> Self {
+        Self::with_resolution(40, 6, 3)
+    }
+
+    /// The sprite is centered on `x`, leaning right when its width is even.
+    pub fn with_resolution(width: usize, height: usize, sprite_width: usize) -> Self {
+        assert!(width > 0 && height > 0, "screen needs at least one pixel");
         CRT {
+            width,
+            height,
+            sprite_width,
             index: 0,
-            drawing: ['.'; 240], //vec![],
+            frames: vec![vec![false; width * height]],
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Lit pixels of the frame being drawn, row by row.
+    pub fn framebuffer(&self) -> &[bool] {
+        self.frames.last().expect("screen has at least one frame")
+    }
+
+    /// Every frame drawn so far, a new one starts once the last is full.
+    pub fn frames(&self) -> &[Vec<bool>] {
+        &self.frames
+    }
+
     pub fn render(&mut self, sprite_pos: i32) {
-        let render_pos = self.index % 40;
+        if self.index == self.width * self.height {
+            self.frames.push(vec![false; self.width * self.height]);
+            self.index = 0;
+        }
 
-        if render_pos == (sprite_pos - 1) as usize
-            || render_pos == sprite_pos as usize
-            || render_pos == (sprite_pos + 1) as usize
-        {
-            self.drawing[self.index] = '#';
-        };
+        let render_pos = (self.index % self.width) as i64;
+        let sprite_start = sprite_pos as i64 - (self.sprite_width as i64 - 1) / 2;
+        if (sprite_start..sprite_start + self.sprite_width as i64).contains(&render_pos) {
+            let frame = self
+                .frames
+                .last_mut()
+                .expect("screen has at least one frame");
+            frame[self.index] = true;
+        }
         self.index += 1;
     }
+}
 
-    pub fn print(self) {
-        for n in 0..6 {
-            let start = n * 40;
-            println!("{:?}", &self.drawing[start..start + 40]);
+impl fmt::Display for CRT {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, frame) in self.frames.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            for row in frame.chunks(self.width) {
+                let row = row
+                    .iter()
+                    .map(|lit| if *lit { '#' } else { '.' })
+                    .collect::<String>();
+                writeln!(f, "{row}")?;
+            }
         }
+
+        Ok(())
     }
 }
 
-#[aoc_generator(day10)]
-fn parse_input(input: &str) -> Vec<Operation> {
-    input
-        .lines()
-        .map(|line| {
-            let mut split = line.split(' ');
-            match split.next() {
-                Some("noop") => Operation::Noop,
-                Some("addx") => Operation::Add(split.next().unwrap().parse::<i32>().unwrap()),
-                _ => unreachable!(),
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+// glyphs are separated by one blank column
+const GLYPH_STEP: usize = GLYPH_WIDTH + 1;
+
+// 4x6 letters of the AoC font, row by row
+const FONT: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Y', "#...#....#.#..#...#...#."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+/// Reads the letters off the top six rows of a `width` pixels wide framebuffer.
+/// Glyphs which aren't part of the font come out as `?`.
+pub fn recognize(framebuffer: &[bool], width: usize) -> String {
+    let pixel = |x: usize, y: usize| x < width && framebuffer.get(y * width + x) == Some(&true);
+
+    (0..width)
+        .step_by(GLYPH_STEP)
+        .map(|left| {
+            let glyph = (0..GLYPH_HEIGHT)
+                .flat_map(|y| (left..left + GLYPH_WIDTH).map(move |x| (x, y)))
+                .map(|(x, y)| if pixel(x, y) { '#' } else { '.' })
+                .collect::<String>();
+
+            FONT.iter()
+                .find(|(_, pattern)| *pattern == glyph)
+                .map_or('?', |(letter, _)| *letter)
+        })
+        .collect()
+}
+
+// values of x worth trying, anything further away from the screen looks the same as these
+const SYNTHESIS_X: std::ops::RangeInclusive<i32> = -2..=41;
+
+#[derive(Clone, Copy)]
+struct SynthesisStep {
+    instructions: usize,
+    // previous state, value of x during the instruction and whether it was addx
+    from: usize,
+    x: i32,
+    addx: bool,
+}
+
+/// Finds the shortest program of `noop` and `addx` whose run draws `image` on the 40x6 CRT in
+/// exactly one frame. Returns `None` for images no program can draw: `x` starts at 1 and can't
+/// change before the end of the second cycle, so the first two pixels are always lit.
+pub fn synthesize(image: &[bool]) -> Option<Vec<Operation>> {
+    const WIDTH: usize = 40;
+    assert_eq!(WIDTH * 6, image.len(), "image must be 40x6 pixels");
+
+    let values = SYNTHESIS_X.collect::<Vec<i32>>();
+    let allowed = |cycle: usize, x: i32| {
+        let lit = (x - (cycle % WIDTH) as i32).abs() <= 1;
+        lit == image[cycle]
+    };
+    // state before a cycle: x in the register and whether the previous instruction was addx,
+    // which could still have set x to anything
+    let state =
+        |cycle: usize, value: usize, free: bool| (cycle * values.len() + value) * 2 + free as usize;
+
+    let mut best: Vec<Option<SynthesisStep>> = vec![None; state(image.len() + 1, 0, false)];
+    let start = values.iter().position(|x| *x == 1)?;
+    best[state(0, start, false)] = Some(SynthesisStep {
+        instructions: 0,
+        from: usize::MAX,
+        x: 1,
+        addx: false,
+    });
+
+    for cycle in 0..image.len() {
+        for value in 0..values.len() {
+            for free in [false, true] {
+                let from = state(cycle, value, free);
+                let Some(step) = best[from] else {
+                    continue;
+                };
+
+                let choices = if free {
+                    0..values.len()
+                } else {
+                    value..value + 1
+                };
+                for next in choices.filter(|next| allowed(cycle, values[*next])) {
+                    let x = values[next];
+                    let mut candidates = vec![(state(cycle + 1, next, false), false)];
+                    if cycle + 1 < image.len() && allowed(cycle + 1, x) {
+                        candidates.push((state(cycle + 2, next, true), true));
+                    }
+
+                    for (to, addx) in candidates {
+                        if best[to].is_none_or(|known| known.instructions > step.instructions + 1) {
+                            best[to] = Some(SynthesisStep {
+                                instructions: step.instructions + 1,
+                                from,
+                                x,
+                                addx,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut current = (0..values.len())
+        .flat_map(|value| {
+            [
+                state(image.len(), value, false),
+                state(image.len(), value, true),
+            ]
+        })
+        .filter(|end| best[*end].is_some())
+        .min_by_key(|end| best[*end].map(|step| step.instructions))?;
+
+    let mut steps = vec![];
+    while let Some(step) = best[current].filter(|step| step.from != usize::MAX) {
+        steps.push(step);
+        current = step.from;
+    }
+    steps.reverse();
+
+    // addx moves x to whatever the following instruction runs with
+    Some(
+        steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| match step.addx {
+                false => Operation::Noop,
+                true => Operation::Add(steps.get(index + 1).map_or(0, |next| next.x - step.x)),
+            })
+            .collect(),
+    )
+}
+
+/// Position of an assembled instruction in the source, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    /// where each instruction starts in the source
+    pub spans: Vec<Span>,
+    /// labels with the index of the instruction they point to, in order of definition
+    pub labels: Vec<(String, usize)>,
+}
+
+struct Statement<'s> {
+    line_number: usize,
+    line: &'s str,
+    tokens: Vec<&'s str>,
+}
+
+/// Assembles one instruction per line. Text after `;` or `#` is a comment, `name:` in front of
+/// an instruction or on its own line defines a label, operands are separated by whitespace or
+/// commas and are registers, labels or numbers such as `-12`, `0x1f`, `0o17` or `0b1_0101`.
+/// Labels stand for the index of the instruction following them.
+pub fn assemble(set: &InstructionSet, source: &str) -> Result<Program, ParseError> {
+    let mut statements = vec![];
+    let mut labels: Vec<(String, usize)> = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split([';', '#']).next().unwrap_or_default();
+        let mut tokens = tokenize(code);
+
+        while let Some(label) = tokens.first().and_then(|token| token.strip_suffix(':')) {
+            if !is_identifier(label) {
+                return Err(ParseError::at(
+                    DAY,
+                    index + 1,
+                    line,
+                    tokens[0],
+                    "invalid label",
+                ));
             }
+            if set.register(label).is_some() {
+                return Err(ParseError::at(
+                    DAY,
+                    index + 1,
+                    line,
+                    label,
+                    "label shadows a register",
+                ));
+            }
+            if labels.iter().any(|(name, _)| name == label) {
+                return Err(ParseError::at(
+                    DAY,
+                    index + 1,
+                    line,
+                    label,
+                    "duplicate label",
+                ));
+            }
+
+            labels.push((label.to_string(), statements.len()));
+            tokens.remove(0);
+        }
+
+        if !tokens.is_empty() {
+            statements.push(Statement {
+                line_number: index + 1,
+                line,
+                tokens,
+            });
+        }
+    }
+
+    let mut instructions = Vec::with_capacity(statements.len());
+    let mut spans = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let Statement {
+            line_number,
+            line,
+            tokens,
+        } = statement;
+        let error = |snippet: &str, message: String| {
+            ParseError::at(DAY, line_number, line, snippet, message)
+        };
+
+        let mnemonic = tokens[0];
+        let opcode = set
+            .opcode(mnemonic)
+            .ok_or_else(|| error(mnemonic, format!("unknown instruction '{mnemonic}'")))?;
+        let kinds = set.definition(opcode).operands;
+
+        if tokens.len() - 1 < kinds.len() {
+            // points right after the last token, comments don't count
+            let last = tokens[tokens.len() - 1];
+            return Err(error(
+                &last[last.len()..],
+                format!("'{mnemonic}' expects {} operands", kinds.len()),
+            ));
+        }
+        if let Some(extra) = tokens.get(kinds.len() + 1) {
+            return Err(error(extra, "unexpected operand".to_string()));
+        }
+
+        let mut operands = Vec::with_capacity(kinds.len());
+        for (token, kind) in tokens[1..].iter().zip(kinds) {
+            let operand = if let Some(register) = set.register(token) {
+                Operand::Register(register)
+            } else if token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+                Operand::Immediate(
+                    parse_literal(token)
+                        .ok_or_else(|| error(token, "invalid number".to_string()))?,
+                )
+            } else if let Some((_, target)) = labels.iter().find(|(name, _)| name == token) {
+                Operand::Immediate(*target as i32)
+            } else {
+                return Err(error(
+                    token,
+                    "expected register, number or label".to_string(),
+                ));
+            };
+
+            if !operand.matches(*kind) {
+                return Err(error(token, format!("expected {kind:?} operand")));
+            }
+            operands.push(operand);
+        }
+
+        instructions.push(
+            set.instruction(mnemonic, &operands)
+                .map_err(|message| error(mnemonic, message))?,
+        );
+        spans.push(Span {
+            line: line_number,
+            column: column_of(line, mnemonic),
+        });
+    }
+
+    Ok(Program {
+        instructions,
+        spans,
+        labels,
+    })
+}
+
+// tokens borrow from `code` so errors can point at them
+fn tokenize(code: &str) -> Vec<&str> {
+    code.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_literal(token: &str) -> Option<i32> {
+    let (negative, unsigned) = match token.as_bytes().first()? {
+        b'-' => (true, &token[1..]),
+        b'+' => (false, &token[1..]),
+        _ => (false, token),
+    };
+
+    let lowercase = unsigned.to_ascii_lowercase();
+    let (radix, digits) = match lowercase.get(..2) {
+        Some("0x") => (16, &lowercase[2..]),
+        Some("0o") => (8, &lowercase[2..]),
+        Some("0b") => (2, &lowercase[2..]),
+        _ => (10, &lowercase[..]),
+    };
+
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+
+    let magnitude = i64::from_str_radix(&digits, radix).ok()?;
+    i32::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+/// Canonical text of the program, one instruction per line.
+pub fn disassemble(operations: &[Operation]) -> String {
+    operations
+        .iter()
+        .map(|operation| format!("{operation}\n"))
+        .collect()
+}
+
+#[aoc_generator(day10)]
+fn parse_input(input: &str) -> Result<Vec<Operation>, ParseError> {
+    let program = assemble(&InstructionSet::puzzle(), input)?;
+
+    Ok(program
+        .instructions
+        .iter()
+        .map(|instruction| match instruction.operands[..] {
+            [] => Operation::Noop,
+            [operand] => Operation::Add(operand.value(&[])),
+            _ => unreachable!("puzzle instructions take at most one operand"),
         })
-        .collect::<Vec<Operation>>()
+        .collect())
 }
 
 #[aoc(day10, part1)]
 pub fn part1(input: &[Operation]) -> i32 {
-    let mut cpu = CPU::<240>::new();
+    let mut cpu = CPU::new();
     input
-        .into_iter()
+        .iter()
         .for_each(|operation| cpu.process_operation(operation));
     cpu.get_signal_strenght(&[20, 60, 100, 140, 180, 220])
         .into_iter()
@@ -116,32 +1140,357 @@ pub fn part1(input: &[Operation]) -> i32 {
 }
 
 #[aoc(day10, part2)]
-pub fn part2(input: &[Operation]) -> usize {
-    let mut cpu = CPU::<240>::new();
+pub fn part2(input: &[Operation]) -> String {
+    let mut cpu = CPU::new();
     let mut crt = CRT::new();
     input
-        .into_iter()
+        .iter()
         .for_each(|operation| cpu.process_operation(operation));
 
-    cpu.history[0..240].iter().for_each(|x| crt.render(*x));
-    crt.print();
-    0
+    cpu.history().iter().for_each(|x| crt.render(*x));
+    recognize(&crt.frames()[0], crt.width())
 }
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(13140, got)
 }
 
 #[test]
 pub fn part2_test() {
-    let _ = part2(&parse_input(TEST_INPUT));
+    // the example draws stripes instead of letters
+    let input = parse_input(TEST_INPUT).unwrap();
+    assert_eq!("????????", part2(&input));
+
+    let mut cpu = CPU::new();
+    let mut crt = CRT::new();
+    input
+        .iter()
+        .for_each(|operation| cpu.process_operation(operation));
+    cpu.history.iter().for_each(|x| crt.render(*x));
+    let want = "\
+##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+";
+    assert_eq!(want, crt.to_string());
+}
+
+#[test]
+fn crt_resolution_test() {
+    // sprite of five pixels around x = 1 is clipped at the left edge
+    let mut crt = CRT::with_resolution(8, 2, 5);
+    (0..20).for_each(|_| crt.render(1));
+
+    assert_eq!(2, crt.frames().len());
+    assert_eq!(4, crt.framebuffer().iter().filter(|lit| **lit).count());
+    assert_eq!(
+        "####....\n####....\n\n####....\n........\n",
+        crt.to_string()
+    );
+
+    let mut crt = CRT::with_resolution(4, 1, 2);
+    (0..4).for_each(|_| crt.render(2));
+    assert_eq!("..##\n", crt.to_string());
+}
+
+#[test]
+fn long_program_test() {
+    let program = vec![Operation::Add(1); 200];
+    let mut cpu = CPU::new();
+    program
+        .iter()
+        .for_each(|operation| cpu.process_operation(operation));
+    assert_eq!(400, cpu.history().len());
+    assert_eq!(
+        vec![0, 20 * 10, 380 * 190, 0],
+        cpu.get_signal_strenght(&[0, 20, 380, 401])
+    );
+
+    let mut crt = CRT::new();
+    cpu.history().iter().for_each(|x| crt.render(*x));
+    assert_eq!(2, crt.frames().len());
+}
+
+#[test]
+fn recognize_test() {
+    // every letter of the font once, rendered the way the CRT lays them out
+    let text = FONT.iter().map(|(letter, _)| *letter).collect::<String>();
+    let width = FONT.len() * GLYPH_STEP;
+    let mut framebuffer = vec![false; width * GLYPH_HEIGHT];
+    for (index, (_, pattern)) in FONT.iter().enumerate() {
+        for (offset, c) in pattern.chars().enumerate() {
+            let (x, y) = (offset % GLYPH_WIDTH, offset / GLYPH_WIDTH);
+            framebuffer[y * width + index * GLYPH_STEP + x] = c == '#';
+        }
+    }
+
+    assert_eq!(text, recognize(&framebuffer, width));
+
+    framebuffer[0] = !framebuffer[0];
+    assert_eq!(format!("?{}", &text[1..]), recognize(&framebuffer, width));
+}
+
+#[cfg(test)]
+fn draw(operations: &[Operation]) -> (CRT, usize) {
+    let mut cpu = CPU::new();
+    operations
+        .iter()
+        .for_each(|operation| cpu.process_operation(operation));
+    let mut crt = CRT::new();
+    cpu.history().iter().for_each(|x| crt.render(*x));
+    (crt, cpu.history().len())
+}
+
+#[test]
+fn synthesize_test() {
+    let operations = parse_input(TEST_INPUT).unwrap();
+    let (crt, _) = draw(&operations);
+    let synthesized = synthesize(crt.framebuffer()).unwrap();
+
+    let (got, cycles) = draw(&synthesized);
+    assert_eq!(crt.framebuffer(), got.framebuffer());
+    assert_eq!(240, cycles);
+    assert!(synthesized.len() <= operations.len());
+
+    // x is 1 during the first two cycles, these pixels can't be dark
+    let mut image = vec![true; 240];
+    image[1] = false;
+    assert_eq!(None, synthesize(&image));
+}
+
+#[test]
+fn synthesize_random_images_test() {
+    let mut next = crate::random::xorshift();
+
+    for round in 0..40 {
+        // from sparse to dense images
+        let density = round % 8 + 1;
+        let mut image = (0..240)
+            .map(|_| next() % 9 < density)
+            .collect::<Vec<bool>>();
+        image[0] = true;
+        image[1] = true;
+
+        let operations = synthesize(&image).unwrap();
+        let (crt, cycles) = draw(&operations);
+        assert_eq!(image, crt.framebuffer(), "round {round}");
+        assert_eq!(240, cycles);
+    }
+}
+
+#[test]
+fn vm_puzzle_program_test() {
+    let set = InstructionSet::standard();
+    let program = assemble(&set, "noop\naddx 3\naddx -5")
+        .unwrap()
+        .instructions;
+    let mut vm = Vm::new(set);
+    let mut during = vec![];
+    vm.run(&program, |cycle, registers| {
+        during.push((cycle, registers[X]))
+    });
+
+    assert_eq!(vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)], during);
+    assert_eq!(Some(-1), vm.register("x"));
+
+    let operations = parse_input(TEST_INPUT).unwrap();
+    let program = operations
+        .iter()
+        .map(Instruction::from)
+        .collect::<Vec<Instruction>>();
+    let mut cpu = CPU::new();
+    operations
+        .iter()
+        .for_each(|operation| cpu.process_operation(operation));
+    let mut vm = Vm::new(InstructionSet::standard());
+    let mut history = vec![];
+    vm.run(&program, |_, registers| history.push(registers[X]));
+
+    assert_eq!(cpu.history.to_vec(), history);
+    assert_eq!(cpu.register_x(), vm.registers()[X]);
+}
+
+#[test]
+fn vm_branch_test() {
+    let set = InstructionSet::standard();
+    // b = 5 + 4 + 3 + 2 + 1
+    let program = assemble(&set, "set a 5\nadd b a\nadd a -1\njnz a 1\njz b 6\nset c 1")
+        .unwrap()
+        .instructions;
+    let mut vm = Vm::new(set);
+    vm.run(&program, |_, _| ());
+
+    assert_eq!(Some(0), vm.register("a"));
+    assert_eq!(Some(15), vm.register("b"));
+    assert_eq!(Some(1), vm.register("c"));
+    assert_eq!(1 + 5 * (2 + 2 + 1) + 1 + 1, vm.cycle());
+    assert_eq!(6, vm.pc());
+}
+
+#[test]
+fn vm_wrapping_test() {
+    let set = InstructionSet::standard();
+    let program = assemble(
+        &set,
+        "set a 100000\nmul a a\nset x 0x7fff_ffff\naddx 1\nadd b -0x8000_0000\nadd b -1",
+    )
+    .unwrap()
+    .instructions;
+    let mut vm = Vm::new(set);
+    vm.run(&program, |_, _| ());
+
+    assert_eq!(Some(100_000i32.wrapping_mul(100_000)), vm.register("a"));
+    assert_eq!(Some(i32::MIN), vm.register("x"));
+    assert_eq!(Some(i32::MAX), vm.register("b"));
+}
+
+#[test]
+fn debugger_trace_test() {
+    let set = InstructionSet::standard();
+    let program = assemble(&set, "noop\naddx 3\naddx -5")
+        .unwrap()
+        .instructions;
+
+    let mut text = vec![];
+    let mut debugger =
+        Debugger::new(set.clone(), &program).with_trace(TraceFormat::Text, &mut text);
+    assert_eq!(Stop::Finished, debugger.resume().unwrap());
+    drop(debugger);
+    let want = "\
+cycle    1  pc   0  noop        x 1 -> 1  during 1
+cycle    2  pc   1  addx 3      x 1 -> 1  during 1
+cycle    3  pc   1  addx 3      x 1 -> 4  during 1
+cycle    4  pc   2  addx -5     x 4 -> 4  during 4
+cycle    5  pc   2  addx -5     x 4 -> -1  during 4
+";
+    assert_eq!(want, String::from_utf8(text).unwrap());
+
+    let mut json = vec![];
+    let mut debugger = Debugger::new(set, &program).with_trace(TraceFormat::JsonLines, &mut json);
+    debugger.step_instruction().unwrap();
+    drop(debugger);
+    assert_eq!(
+        "{\"cycle\":1,\"pc\":0,\"instruction\":\"noop\",\"x_before\":1,\"x_during\":1,\"x_after\":1}\n",
+        String::from_utf8(json).unwrap()
+    );
+}
+
+#[test]
+fn debugger_breakpoint_test() {
+    let program = parse_input(TEST_INPUT)
+        .unwrap()
+        .iter()
+        .map(Instruction::from)
+        .collect::<Vec<Instruction>>();
+    let mut debugger = Debugger::new(InstructionSet::standard(), &program);
+    debugger.add_breakpoint(Breakpoint::Cycle(1));
+    debugger.add_breakpoint(Breakpoint::Cycle(20));
+    debugger.add_breakpoint(Breakpoint::Instruction(9));
+
+    assert_eq!(
+        Stop::Breakpoint(Breakpoint::Cycle(1)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(0, debugger.vm().cycle());
+
+    // the noop at index 9 starts at cycle 19
+    assert_eq!(
+        Stop::Breakpoint(Breakpoint::Instruction(9)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(18, debugger.vm().cycle());
+
+    // signal strength of cycle 20 is 20 * 21, the value of x during that cycle
+    assert_eq!(
+        Stop::Breakpoint(Breakpoint::Cycle(20)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(21, debugger.vm().registers()[X]);
+    let entry = debugger.step_cycle().unwrap().unwrap();
+    assert_eq!((20, 21), (entry.cycle, entry.during));
+
+    debugger.clear();
+    assert_eq!(Stop::Finished, debugger.resume().unwrap());
+    assert_eq!(240, debugger.vm().cycle());
+    assert!(!debugger.step_instruction().unwrap());
+}
+
+#[test]
+fn debugger_watchpoint_test() {
+    let set = InstructionSet::standard();
+    let program = assemble(&set, "noop\naddx 3\naddx -5\naddx 5")
+        .unwrap()
+        .instructions;
+    let mut debugger = Debugger::new(set, &program);
+    assert_eq!(
+        Err("unknown register 5".to_string()),
+        debugger.add_watchpoint(Watchpoint::Changed(5))
+    );
+    debugger.add_watchpoint(Watchpoint::Equals(X, 4)).unwrap();
+
+    assert_eq!(
+        Stop::Watchpoint(Watchpoint::Equals(X, 4)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(3, debugger.vm().cycle());
+
+    debugger.add_watchpoint(Watchpoint::Changed(X)).unwrap();
+    assert_eq!(
+        Stop::Watchpoint(Watchpoint::Changed(X)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(
+        (5, -1),
+        (debugger.vm().cycle(), debugger.vm().registers()[X])
+    );
+
+    // x is back at 4, both watchpoints fire and the first one wins
+    assert_eq!(
+        Stop::Watchpoint(Watchpoint::Equals(X, 4)),
+        debugger.resume().unwrap()
+    );
+    assert_eq!(Stop::Finished, debugger.resume().unwrap());
+}
+
+#[test]
+fn instruction_set_test() {
+    let set = InstructionSet::new()
+        .with_register("acc", 0)
+        .with_instruction(NOOP)
+        .with_instruction(InstructionDef {
+            mnemonic: "double",
+            operands: &[OperandKind::Register],
+            cycles: 3,
+            effect: |registers, operands| {
+                registers[operands[0].register()] *= 2;
+                Flow::Next
+            },
+        });
+
+    assert!(set.instruction("addx", &[Operand::Immediate(1)]).is_err());
+    assert!(set.instruction("double", &[Operand::Immediate(1)]).is_err());
+    assert!(set.instruction("double", &[Operand::Register(1)]).is_err());
+    assert!(set.instruction("noop", &[Operand::Register(0)]).is_err());
+
+    let program = vec![
+        set.instruction("double", &[Operand::Register(0)]).unwrap(),
+        set.instruction("noop", &[]).unwrap(),
+    ];
+    let mut vm = Vm::new(set.with_register("unused", 7));
+    vm.registers[0] = 3;
+    vm.run(&program, |_, _| ());
+    assert_eq!(vec![6, 7], vm.registers());
+    assert_eq!(4, vm.cycle());
 }
 
 #[test]
 fn parse_input_test() {
-    let got = parse_input(TEST_INPUT);
+    let got = parse_input(TEST_INPUT).unwrap();
     let want = vec![
         Operation::Add(15),
         Operation::Add(-11),
@@ -293,6 +1642,125 @@ fn parse_input_test() {
     assert_eq!(want, got)
 }
 
+#[test]
+fn assemble_test() {
+    let set = InstructionSet::standard();
+    let source = r#"; sum of a countdown
+start:  set a, 0x5      # hex
+        set d 0b1_0
+loop:   add b a
+        add a -1
+        jnz a loop
+done:
+end:    mul b +0o10     ; octal
+"#;
+    let program = assemble(&set, source).unwrap();
+
+    assert_eq!(
+        vec![
+            ("start".to_string(), 0),
+            ("loop".to_string(), 2),
+            ("done".to_string(), 5),
+            ("end".to_string(), 5),
+        ],
+        program.labels
+    );
+    assert_eq!(
+        set.instruction("jnz", &[Operand::Register(1), Operand::Immediate(2)])
+            .unwrap(),
+        program.instructions[4]
+    );
+    assert_eq!(Span { line: 2, column: 9 }, program.spans[0]);
+    assert_eq!(Span { line: 8, column: 9 }, program.spans[5]);
+    // columns count characters, like error columns do
+    let spaced = assemble(&set, "top:\u{3000}add a 1").unwrap();
+    assert_eq!(Span { line: 1, column: 6 }, spaced.spans[0]);
+    let error = assemble(&set, "top:\u{3000}mov a 1").unwrap_err();
+    assert_eq!((1, 6), (error.line, error.column));
+
+    let mut vm = Vm::new(set);
+    vm.run(&program.instructions, |_, _| ());
+    assert_eq!(Some(15 * 8), vm.register("b"));
+    assert_eq!(Some(2), vm.register("d"));
+}
+
+#[test]
+fn assemble_error_test() {
+    let set = InstructionSet::standard();
+    let error = |source| {
+        let error = assemble(&set, source).unwrap_err();
+        (error.line, error.column, error.message)
+    };
+
+    assert_eq!(
+        (2, 1, "unknown instruction 'mov'".to_string()),
+        error("noop\nmov a 1")
+    );
+    assert_eq!((1, 7, "invalid number".to_string()), error("set a 0xZZ"));
+    assert_eq!(
+        (1, 7, "invalid number".to_string()),
+        error("set a 3000000000")
+    );
+    assert_eq!(
+        (1, 7, "expected register, number or label".to_string()),
+        error("jnz a nowhere")
+    );
+    assert_eq!(
+        (2, 1, "duplicate label".to_string()),
+        error("top: noop\ntop: noop")
+    );
+    assert_eq!(
+        (1, 1, "label shadows a register".to_string()),
+        error("x: noop")
+    );
+    assert_eq!((1, 9, "unexpected operand".to_string()), error("addx 1, 2"));
+    assert_eq!(
+        (1, 6, "'set' expects 2 operands".to_string()),
+        error("set a ; b")
+    );
+    assert_eq!(
+        (1, 5, "expected Register operand".to_string()),
+        error("add 1 a")
+    );
+}
+
+#[test]
+fn parse_literal_test() {
+    assert_eq!(Some(-42), parse_literal("-42"));
+    assert_eq!(Some(255), parse_literal("0xFF"));
+    assert_eq!(Some(-8), parse_literal("-0o10"));
+    assert_eq!(Some(5), parse_literal("0b101"));
+    assert_eq!(Some(i32::MIN), parse_literal("-0x8000_0000"));
+    assert_eq!(None, parse_literal("0x"));
+    assert_eq!(None, parse_literal("--1"));
+    assert_eq!(None, parse_literal("0x-1"));
+    assert_eq!(None, parse_literal("12a"));
+}
+
+#[test]
+fn disassemble_test() {
+    let operations = parse_input(TEST_INPUT).unwrap();
+    let text = disassemble(&operations);
+    assert_eq!(format!("{TEST_INPUT}\n"), text);
+    assert_eq!(operations, parse_input(&text).unwrap());
+
+    let operations = parse_input("noop ; comment\nloop: addx 0x10\naddx -0b11").unwrap();
+    assert_eq!("noop\naddx 16\naddx -3\n", disassemble(&operations));
+}
+
+#[test]
+fn parse_input_error_test() {
+    let got = parse_input("noop\nadd 3").unwrap_err();
+    assert_eq!((2, 1), (got.line, got.column));
+    assert_eq!("add", got.snippet);
+
+    let got = parse_input("noop\naddx 3\naddx x").unwrap_err();
+    assert_eq!((3, 6), (got.line, got.column));
+
+    let got = parse_input("addx").unwrap_err();
+    assert_eq!((1, 5), (got.line, got.column));
+}
+
 #[cfg(test)]
 const TEST_INPUT: &str = r#"addx 15
 addx -11
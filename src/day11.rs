@@ -1,190 +1,1581 @@
-use std::{cell::RefCell, collections::VecDeque, str::Lines};
+use crate::error::{parse_number, ParseError};
+use num_bigint::BigUint;
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+const DAY: u8 = 11;
 
 type MonkeyID = usize;
 
-pub struct Monkey {
-    items: RefCell<VecDeque<u64>>,
-    operation: Box<dyn Fn(u64) -> u64>,
-    test: Box<dyn Fn(usize) -> usize>,
-    inspect_count: RefCell<u64>,
-    div: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Operator {
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Sub),
+            "*" => Some(Operator::Mul),
+            "/" => Some(Operator::Div),
+            "%" => Some(Operator::Rem),
+            _ => None,
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Add | Operator::Sub => 1,
+            Operator::Mul | Operator::Div | Operator::Rem => 2,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Rem => "%",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorryError {
+    /// the result doesn't fit, or would be negative
+    Overflow(Operator),
+    DivisionByZero,
+    /// the operator can't be computed in this representation
+    Unsupported(Operator),
+}
+
+impl fmt::Display for WorryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorryError::Overflow(operator) => write!(f, "worry level overflowed on '{operator}'"),
+            WorryError::DivisionByZero => write!(f, "worry level divided by zero"),
+            WorryError::Unsupported(operator) => {
+                write!(f, "'{operator}' isn't supported by this worry level")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorryError {}
+
+/// Number type of worry levels.
+pub trait Worry: Clone + fmt::Debug + fmt::Display {
+    /// `value` in the same representation as `self`.
+    fn lift(&self, value: u64) -> Self;
+    fn apply(&self, operator: Operator, rhs: &Self) -> Result<Self, WorryError>;
+    fn divisible_by(&self, divisor: u64) -> bool;
 }
 
-impl Monkey {
-    pub fn throw(&self) -> Option<(MonkeyID, u64)> {
-        let Some(item) = self.items.borrow_mut().pop_front() else {return None;};
+/// The fast path, any overflow is reported instead of wrapping around.
+impl Worry for u64 {
+    fn lift(&self, value: u64) -> Self {
+        value
+    }
+
+    fn apply(&self, operator: Operator, rhs: &Self) -> Result<Self, WorryError> {
+        let result = match operator {
+            Operator::Div | Operator::Rem if *rhs == 0 => return Err(WorryError::DivisionByZero),
+            Operator::Add => self.checked_add(*rhs),
+            Operator::Sub => self.checked_sub(*rhs),
+            Operator::Mul => self.checked_mul(*rhs),
+            Operator::Div => self.checked_div(*rhs),
+            Operator::Rem => self.checked_rem(*rhs),
+        };
+
+        result.ok_or(WorryError::Overflow(operator))
+    }
 
-        self.inspect_count.replace_with(|&mut old| old + 1);
+    fn divisible_by(&self, divisor: u64) -> bool {
+        self.is_multiple_of(divisor)
+    }
+}
 
-        let new_item = (self.operation)(item) / 3;
+/// Always exact, but worry levels which keep growing get slow.
+impl Worry for BigUint {
+    fn lift(&self, value: u64) -> Self {
+        BigUint::from(value)
+    }
 
-        let monkey_id = (self.test)(new_item as usize);
+    fn apply(&self, operator: Operator, rhs: &Self) -> Result<Self, WorryError> {
+        let zero = BigUint::from(0u8);
+        match operator {
+            Operator::Div | Operator::Rem if *rhs == zero => Err(WorryError::DivisionByZero),
+            Operator::Sub if self < rhs => Err(WorryError::Overflow(operator)),
+            Operator::Add => Ok(self + rhs),
+            Operator::Sub => Ok(self - rhs),
+            Operator::Mul => Ok(self * rhs),
+            Operator::Div => Ok(self / rhs),
+            Operator::Rem => Ok(self % rhs),
+        }
+    }
 
-        Some((monkey_id, new_item))
+    fn divisible_by(&self, divisor: u64) -> bool {
+        self % divisor == BigUint::from(0u8)
     }
+}
 
-    // println!("item={item}, new_item={new_item}, monkey_id={monkey_id}");
+/// Worry level modulo a multiple of every divisor the monkeys test, which keeps the tests right
+/// while the numbers stay small. Division and remainder don't survive the reduction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Modular {
+    pub fn new(value: u64, modulus: u64) -> Self {
+        assert!(modulus > 0, "modulus must be positive");
+        Modular {
+            value: value % modulus,
+            modulus,
+        }
+    }
 
-    pub fn throw_p2(&self) -> Option<(MonkeyID, u64)> {
-        let Some(item) = self.items.borrow_mut().pop_front() else {return None;};
-        self.inspect_count.replace_with(|old| *old + 1);
-        let new_item = (self.operation)(item);
-        let pass_to = (self.test)(new_item as usize);
-        Some((pass_to, new_item))
+    pub fn value(&self) -> u64 {
+        self.value
     }
+}
 
-    pub fn catch(&self, item: u64) {
-        self.items.borrow_mut().push_back(item);
+impl fmt::Display for Modular {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
     }
 }
 
-fn parse_starting_items(line: &str) -> VecDeque<u64> {
-    let mut ns = vec![];
-    line.split(":")
-        .skip(1)
-        .map(|n| {
-            n.split(",")
-                .map(|n| n.trim().parse::<u64>().unwrap())
-                .collect::<Vec<u64>>()
+impl Worry for Modular {
+    fn lift(&self, value: u64) -> Self {
+        Modular::new(value, self.modulus)
+    }
+
+    fn apply(&self, operator: Operator, rhs: &Self) -> Result<Self, WorryError> {
+        let (lhs, rhs, modulus) = (self.value as u128, rhs.value as u128, self.modulus as u128);
+        let value = match operator {
+            Operator::Add => (lhs + rhs) % modulus,
+            Operator::Sub => (lhs + modulus - rhs) % modulus,
+            Operator::Mul => (lhs * rhs) % modulus,
+            Operator::Div | Operator::Rem => return Err(WorryError::Unsupported(operator)),
+        };
+
+        Ok(Modular {
+            value: value as u64,
+            modulus: self.modulus,
         })
-        .for_each(|v| v.iter().for_each(|n| ns.push(*n)));
+    }
 
-    ns.into()
+    fn divisible_by(&self, divisor: u64) -> bool {
+        debug_assert!(self.modulus.is_multiple_of(divisor));
+        self.value.is_multiple_of(divisor)
+    }
 }
 
-fn parse_operation(input: &str) -> Box<dyn Fn(u64) -> u64> {
-    let mut split = input.trim().split(" ").skip(4);
-    let operator = split.next().expect("no operator for operation");
-    let value = split
-        .next()
-        .expect("missing value for operation (number or 'old')");
+/// Right hand side of a monkey's `new = ...` operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Constant(u64),
+    Old,
+    Binary(Box<Expr>, Operator, Box<Expr>),
+}
 
-    if operator.trim() == "+" {
-        return match value {
-            "old" => Box::new(|x| x + x),
-            _ => {
-                let value = value.parse::<u64>().unwrap();
-                Box::new(move |x| x + value)
-            }
+impl Expr {
+    pub fn eval<W: Worry>(&self, old: &W) -> Result<W, WorryError> {
+        match self {
+            Expr::Constant(value) => Ok(old.lift(*value)),
+            Expr::Old => Ok(old.clone()),
+            Expr::Binary(lhs, operator, rhs) => lhs.eval(old)?.apply(*operator, &rhs.eval(old)?),
+        }
+    }
+
+    /// Parses `expression`, a part of `line`, so errors point into the whole line.
+    pub fn parse(line_number: usize, line: &str, expression: &str) -> Result<Self, ParseError> {
+        let mut parser = ExprParser {
+            line_number,
+            line,
+            tokens: tokenize(expression),
+            position: 0,
         };
+
+        let expr = parser.expression()?;
+        match parser.tokens.get(parser.position) {
+            Some(token) => Err(parser.error(token, "unexpected token")),
+            None => Ok(expr),
+        }
     }
 
-    match value {
-        "old" => Box::new(|x| x * x),
-        _ => {
-            let value = value.parse::<u64>().unwrap();
-            Box::new(move |x| x * value)
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(_, operator, _) => operator.precedence(),
+            _ => u8::MAX,
         }
     }
 }
 
-fn parse_test(lines: &mut Lines) -> (Box<dyn Fn(usize) -> usize>, usize) {
-    fn get_last(line: &str) -> usize {
-        line.split(" ").last().unwrap().parse::<usize>().unwrap()
-    }
+impl FromStr for Expr {
+    type Err = ParseError;
 
-    let divisible_num = get_last(lines.next().expect("missing 'divisible by' line"));
-    let true_cond = get_last(
-        lines
-            .next()
-            .expect("missing true condition description line"),
-    );
-    let false_cond = get_last(
-        lines
-            .next()
-            .expect("missing false condition description line"),
-    );
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(1, s, s)
+    }
+}
 
-    (
-        Box::new(move |x| {
-            if x % divisible_num == 0 {
-                true_cond
-            } else {
-                false_cond
+// only the parentheses needed to keep the tree, operators are left associative
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Constant(value) => write!(f, "{value}"),
+            Expr::Old => write!(f, "old"),
+            Expr::Binary(lhs, operator, rhs) => {
+                match lhs.precedence() < operator.precedence() {
+                    true => write!(f, "({lhs})")?,
+                    false => write!(f, "{lhs}")?,
+                }
+                write!(f, " {operator} ")?;
+                match rhs.precedence() <= operator.precedence() {
+                    true => write!(f, "({rhs})"),
+                    false => write!(f, "{rhs}"),
+                }
             }
-        }),
-        divisible_num,
-    )
+        }
+    }
 }
 
-#[aoc_generator(day11)]
-pub fn parse_input(input: &str) -> Vec<Monkey> {
-    input
-        .split("\n\n")
-        .collect::<Vec<&str>>()
-        .into_iter()
-        .map(|monkey_description| {
-            let mut lines = monkey_description.lines();
-            lines.next(); // monkey number, we can skip
-            let items = parse_starting_items(lines.next().unwrap());
-            let operation = parse_operation(lines.next().unwrap());
-            let (test, div) = parse_test(&mut lines);
-
-            Monkey {
-                items: RefCell::new(items),
-                operation,
-                test,
-                inspect_count: RefCell::new(0),
-                div,
+fn tokenize(expression: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        if c.is_ascii_alphanumeric() {
+            while let Some((position, next)) = chars.next_if(|(_, next)| {
+                next.is_ascii_alphanumeric() && next.is_ascii_digit() == c.is_ascii_digit()
+            }) {
+                end = position + next.len_utf8();
             }
-        })
-        .collect::<Vec<Monkey>>()
+        }
+        tokens.push(&expression[start..end]);
+    }
+
+    tokens
 }
 
-#[aoc(day11, part1)]
-pub fn part1(input: &[Monkey]) -> u64 {
-    for _ in 0..20 {
-        input.iter().for_each(|monkey| {
-            while let Some((monkey_id, item)) = monkey.throw() {
-                input[monkey_id].catch(item);
+// expression = term (('+' | '-') term)*
+// term       = factor (('*' | '/' | '%') factor)*
+// factor     = number | 'old' | '(' expression ')'
+struct ExprParser<'i> {
+    line_number: usize,
+    line: &'i str,
+    tokens: Vec<&'i str>,
+    position: usize,
+}
+
+impl<'i> ExprParser<'i> {
+    fn error(&self, token: &str, message: &str) -> ParseError {
+        ParseError::at(DAY, self.line_number, self.line, token, message)
+    }
+
+    fn binary(
+        &mut self,
+        precedence: u8,
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let mut lhs = operand(self)?;
+
+        while let Some(operator) = self
+            .tokens
+            .get(self.position)
+            .and_then(|token| Operator::from_token(token))
+            .filter(|operator| operator.precedence() == precedence)
+        {
+            self.position += 1;
+            let rhs = operand(self)?;
+            lhs = Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.binary(1, Self::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        self.binary(2, Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        let Some(token) = self.tokens.get(self.position).copied() else {
+            return Err(ParseError::end_of_line(
+                DAY,
+                self.line_number,
+                self.line,
+                "expected number, 'old' or '('",
+            ));
+        };
+        self.position += 1;
+
+        match token {
+            "old" => Ok(Expr::Old),
+            "(" => {
+                let expr = self.expression()?;
+                match self.tokens.get(self.position) {
+                    Some(&")") => {
+                        self.position += 1;
+                        Ok(expr)
+                    }
+                    Some(token) => Err(self.error(token, "expected ')'")),
+                    None => Err(ParseError::end_of_line(
+                        DAY,
+                        self.line_number,
+                        self.line,
+                        "expected ')'",
+                    )),
+                }
             }
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => Ok(Expr::Constant(
+                parse_number(DAY, self.line_number, self.line, token)?,
+            )),
+            _ => Err(self.error(token, "expected number, 'old' or '('")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monkey<W = u64> {
+    id: MonkeyID,
+    items: VecDeque<W>,
+    operation: Expr,
+    inspect_count: u64,
+    div: u64,
+    if_true: MonkeyID,
+    if_false: MonkeyID,
+}
+
+type Throw<W> = Result<Option<(MonkeyID, W)>, WorryError>;
+
+impl<W: Worry> Monkey<W> {
+    /// Inspects the next item, applying the relief if there is any, and says where it goes.
+    pub fn throw(&mut self, relief: Option<&Expr>) -> Throw<W> {
+        self.throw_with(relief, &mut ())
+    }
+
+    fn throw_with(&mut self, relief: Option<&Expr>, observer: &mut impl Observer<W>) -> Throw<W> {
+        let Some(item) = self.items.pop_front() else {
+            return Ok(None);
+        };
+
+        self.inspect_count += 1;
+
+        self.inspect(&item, relief, observer).map(Some)
+    }
+
+    pub fn catch(&mut self, item: W) {
+        self.items.push_back(item);
+    }
+
+    pub fn items(&self) -> &VecDeque<W> {
+        &self.items
+    }
+
+    pub fn inspect_count(&self) -> u64 {
+        self.inspect_count
+    }
+
+    // new worry level of the item and the monkey it's thrown to, the monkey itself stays untouched
+    fn inspect(
+        &self,
+        item: &W,
+        relief: Option<&Expr>,
+        observer: &mut impl Observer<W>,
+    ) -> Result<(MonkeyID, W), WorryError> {
+        observer.event(Event::Inspect {
+            monkey: self.id,
+            worry: item,
+        });
+        let mut new_item = self.operation.eval(item)?;
+        observer.event(Event::Operation {
+            operation: &self.operation,
+            worry: &new_item,
+        });
+
+        if let Some(relief) = relief {
+            new_item = relief.eval(&new_item)?;
+            observer.event(Event::Relief {
+                relief,
+                worry: &new_item,
+            });
+        }
+
+        let divisible = new_item.divisible_by(self.div);
+        observer.event(Event::Test {
+            divisor: self.div,
+            divisible,
+        });
+
+        let target = if divisible {
+            self.if_true
+        } else {
+            self.if_false
+        };
+        observer.event(Event::Throw {
+            worry: &new_item,
+            to: target,
+        });
+
+        Ok((target, new_item))
+    }
+
+    /// The same monkey holding its items in another representation.
+    pub fn convert<V>(&self, convert: impl Fn(&W) -> V) -> Monkey<V> {
+        Monkey {
+            id: self.id,
+            items: self.items.iter().map(convert).collect(),
+            operation: self.operation.clone(),
+            inspect_count: self.inspect_count,
+            div: self.div,
+            if_true: self.if_true,
+            if_false: self.if_false,
+        }
+    }
+}
+
+/// All the monkeys, each one at the index of its id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Troop<W = u64> {
+    monkeys: Vec<Monkey<W>>,
+}
+
+impl<W: Worry> Troop<W> {
+    pub fn monkeys(&self) -> &[Monkey<W>] {
+        &self.monkeys
+    }
+
+    pub fn convert<V>(&self, convert: impl Fn(&W) -> V) -> Troop<V> {
+        Troop {
+            monkeys: self
+                .monkeys
+                .iter()
+                .map(|monkey| monkey.convert(&convert))
+                .collect(),
+        }
+    }
+
+    /// Product of every divisor the monkeys test, worry levels modulo it take the same paths.
+    pub fn modulus(&self) -> Result<u64, WorryError> {
+        self.monkeys.iter().try_fold(1u64, |acc, monkey| {
+            acc.checked_mul(monkey.div)
+                .ok_or(WorryError::Overflow(Operator::Mul))
         })
     }
 
-    let mut inspection_counts = input
-        .iter()
-        .map(|monkey| *monkey.inspect_count.borrow())
-        .collect::<Vec<u64>>();
+    /// Least common multiple of the divisors, the smallest modulus keeping every test right.
+    pub fn lcm(&self) -> Result<u64, WorryError> {
+        self.monkeys.iter().try_fold(1u64, |acc, monkey| {
+            (acc / gcd(acc, monkey.div))
+                .checked_mul(monkey.div)
+                .ok_or(WorryError::Overflow(Operator::Mul))
+        })
+    }
 
-    inspection_counts.sort();
-    let last = inspection_counts.len();
-    inspection_counts[last - 1] * inspection_counts[last - 2]
+    pub fn round(&mut self, config: &SimulationConfig) -> Result<(), WorryError> {
+        self.round_with(config, &mut ())
+    }
+
+    /// Plays a round reporting everything that happens to the observer.
+    pub fn round_with(
+        &mut self,
+        config: &SimulationConfig,
+        observer: &mut impl Observer<W>,
+    ) -> Result<(), WorryError> {
+        for id in 0..self.monkeys.len() {
+            observer.event(Event::Turn { monkey: id });
+
+            while let Some((monkey_id, item)) =
+                self.monkeys[id].throw_with(config.relief.as_ref(), observer)?
+            {
+                self.monkeys[monkey_id].catch(item);
+            }
+        }
+
+        observer.event(Event::RoundEnd { troop: self });
+        Ok(())
+    }
+
+    pub fn inspection_counts(&self) -> Vec<u64> {
+        self.monkeys.iter().map(Monkey::inspect_count).collect()
+    }
+
+    fn items(&self) -> Vec<(MonkeyID, &W)> {
+        self.monkeys
+            .iter()
+            .flat_map(|monkey| monkey.items.iter().map(|item| (monkey.id, item)))
+            .collect()
+    }
+
+    // Moves a single item through one round, reporting each monkey inspecting it. Thrown to a
+    // monkey that hasn't had its turn yet, the item gets inspected again in the same round.
+    fn item_round(
+        &self,
+        mut holder: MonkeyID,
+        mut worry: W,
+        relief: Option<&Expr>,
+        mut inspected_by: impl FnMut(MonkeyID),
+    ) -> Result<(MonkeyID, W), WorryError> {
+        loop {
+            inspected_by(holder);
+            let (target, new_worry) = self.monkeys[holder].inspect(&worry, relief, &mut ())?;
+            worry = new_worry;
+            let next_round = target < holder;
+            holder = target;
+
+            if next_round {
+                return Ok((holder, worry));
+            }
+        }
+    }
+
+    fn follow(
+        &self,
+        mut holder: MonkeyID,
+        mut worry: W,
+        config: &SimulationConfig,
+    ) -> Result<Vec<u64>, WorryError> {
+        let relief = config.relief.as_ref();
+        let mut counts = vec![0; self.monkeys.len()];
+        for _ in 0..config.rounds {
+            (holder, worry) = self.item_round(holder, worry, relief, |id| counts[id] += 1)?;
+        }
+
+        Ok(counts)
+    }
 }
 
-#[aoc(day11, part2)]
-pub fn part2(input: &[Monkey]) -> u64 {
-    let modulo = input.iter().fold(1, |acc, monkey| acc * monkey.div) as u64;
-    for _ in 0..10000 {
-        input.iter().for_each(|monkey| {
-            while let Some((monkey_id, item)) = monkey.throw_p2() {
-                let destressed = item % modulo;
-                input[monkey_id].catch(destressed);
+impl<W: Worry + Send + Sync> Troop<W> {
+    /// Inspection counts after all the configured rounds. Items never interact, so each one is
+    /// followed on its own and the items are spread over the rayon thread pool.
+    pub fn par_inspections(&self, config: &SimulationConfig) -> Result<Vec<u64>, WorryError> {
+        self.items()
+            .into_par_iter()
+            .map(|(holder, item)| self.follow(holder, item.clone(), config))
+            .try_reduce(
+                || vec![0; self.monkeys.len()],
+                |acc, counts| Ok(add(acc, counts)),
+            )
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn add(mut counts: Vec<u64>, other: Vec<u64>) -> Vec<u64> {
+    for (count, other) in counts.iter_mut().zip(other) {
+        *count += other;
+    }
+    counts
+}
+
+/// Everything that happens during an observed round, in the order it happens.
+#[derive(Debug)]
+pub enum Event<'e, W> {
+    Turn { monkey: MonkeyID },
+    Inspect { monkey: MonkeyID, worry: &'e W },
+    Operation { operation: &'e Expr, worry: &'e W },
+    Relief { relief: &'e Expr, worry: &'e W },
+    Test { divisor: u64, divisible: bool },
+    Throw { worry: &'e W, to: MonkeyID },
+    RoundEnd { troop: &'e Troop<W> },
+}
+
+pub trait Observer<W> {
+    fn event(&mut self, event: Event<'_, W>);
+}
+
+impl<W> Observer<W> for () {
+    fn event(&mut self, _: Event<'_, W>) {}
+}
+
+/// Reproduces the puzzle's walkthrough of every inspection, "Monkey 0:" and so on.
+#[derive(Debug, Default)]
+pub struct Verbose {
+    text: String,
+}
+
+impl Verbose {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<W: Worry> Observer<W> for Verbose {
+    fn event(&mut self, event: Event<'_, W>) {
+        let text = &mut self.text;
+        let _ = match event {
+            Event::Turn { monkey } => writeln!(text, "Monkey {monkey}:"),
+            Event::Inspect { worry, .. } => writeln!(
+                text,
+                "  Monkey inspects an item with a worry level of {worry}."
+            ),
+            Event::Operation { operation, worry } => {
+                writeln!(text, "    Worry level {} to {worry}.", describe(operation))
             }
+            Event::Relief { relief, worry } => writeln!(
+                text,
+                "    Monkey gets bored with item. Worry level {} to {worry}.",
+                describe(relief)
+            ),
+            Event::Test { divisor, divisible } => writeln!(
+                text,
+                "    Current worry level is {}divisible by {divisor}.",
+                if divisible { "" } else { "not " }
+            ),
+            Event::Throw { worry, to } => writeln!(
+                text,
+                "    Item with worry level {worry} is thrown to monkey {to}."
+            ),
+            Event::RoundEnd { .. } => Ok(()),
+        };
+    }
+}
+
+// the puzzle's wording for the operations it uses, anything else is spelled out
+fn describe(operation: &Expr) -> String {
+    let Expr::Binary(lhs, operator, rhs) = operation else {
+        return format!("is set to {operation}");
+    };
+
+    match (lhs.as_ref(), operator, rhs.as_ref()) {
+        (Expr::Old, Operator::Mul, Expr::Old) => "is multiplied by itself".to_string(),
+        (Expr::Old, Operator::Add, Expr::Old) => "increases by itself".to_string(),
+        (Expr::Old, Operator::Mul, Expr::Constant(value)) => format!("is multiplied by {value}"),
+        (Expr::Old, Operator::Add, Expr::Constant(value)) => format!("increases by {value}"),
+        (Expr::Old, Operator::Sub, Expr::Constant(value)) => format!("decreases by {value}"),
+        (Expr::Old, Operator::Div, Expr::Constant(value)) => format!("is divided by {value}"),
+        _ => format!("is set to {operation}"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Summary {
+    /// "After round 1, the monkeys are holding items with these worry levels:"
+    Items,
+    /// "== After round 1 ==" followed by how many items each monkey inspected so far
+    Inspections,
+}
+
+/// Writes a summary after every round, or only after the chosen ones.
+#[derive(Debug)]
+pub struct RoundSummary {
+    summary: Summary,
+    rounds: Vec<usize>,
+    round: usize,
+    text: String,
+}
+
+impl RoundSummary {
+    pub fn new(summary: Summary) -> Self {
+        RoundSummary {
+            summary,
+            rounds: vec![],
+            round: 0,
+            text: String::new(),
+        }
+    }
+
+    /// Only summarizes these rounds, counted from 1.
+    pub fn at(mut self, rounds: &[usize]) -> Self {
+        self.rounds = rounds.to_vec();
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<W: Worry> Observer<W> for RoundSummary {
+    fn event(&mut self, event: Event<'_, W>) {
+        let Event::RoundEnd { troop } = event else {
+            return;
+        };
+
+        self.round += 1;
+        if !self.rounds.is_empty() && !self.rounds.contains(&self.round) {
+            return;
+        }
+
+        let text = &mut self.text;
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        let _ = match self.summary {
+            Summary::Items => writeln!(
+                text,
+                "After round {}, the monkeys are holding items with these worry levels:",
+                self.round
+            ),
+            Summary::Inspections => writeln!(text, "== After round {} ==", self.round),
+        };
+
+        for monkey in troop.monkeys() {
+            let _ = match self.summary {
+                Summary::Items => writeln!(
+                    text,
+                    "Monkey {}: {}",
+                    monkey.id,
+                    monkey
+                        .items
+                        .iter()
+                        .map(|item| item.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+                Summary::Inspections => writeln!(
+                    text,
+                    "Monkey {} inspected items {} times.",
+                    monkey.id, monkey.inspect_count
+                ),
+            };
+        }
+    }
+}
+
+/// Writes the monkey back in the puzzle's notation, with the items it holds right now.
+impl<W: Worry> fmt::Display for Monkey<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items = self
+            .items
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(f, "Monkey {}:", self.id)?;
+        writeln!(f, "  Starting items: {items}")?;
+        writeln!(f, "  Operation: new = {}", self.operation)?;
+        writeln!(f, "  Test: divisible by {}", self.div)?;
+        writeln!(f, "    If true: throw to monkey {}", self.if_true)?;
+        writeln!(f, "    If false: throw to monkey {}", self.if_false)
+    }
+}
+
+type Line<'i> = (usize, &'i str);
+
+fn expect_line<'i>(
+    lines: &mut impl Iterator<Item = Line<'i>>,
+    last_line: &mut usize,
+    prefix: &str,
+) -> Result<(Line<'i>, &'i str), ParseError> {
+    let Some((index, line)) = lines.next() else {
+        return Err(ParseError::missing_line(
+            DAY,
+            *last_line + 1,
+            format!("expected '{prefix}'"),
+        ));
+    };
+    *last_line = index + 1;
+
+    let trimmed = line.trim_start();
+    match trimmed.strip_prefix(prefix) {
+        Some(rest) => Ok(((index + 1, line), rest.trim())),
+        None => Err(ParseError::at(
+            DAY,
+            index + 1,
+            line,
+            trimmed,
+            format!("expected '{prefix}'"),
+        )),
+    }
+}
+
+fn parse_starting_items(
+    (line_number, line): Line,
+    items: &str,
+) -> Result<VecDeque<u64>, ParseError> {
+    // a monkey may hold no items at all
+    if items.is_empty() {
+        return Ok(VecDeque::new());
+    }
+
+    items
+        .split(',')
+        .map(|n| parse_number::<u64>(DAY, line_number, line, n.trim()))
+        .collect()
+}
+
+fn parse_test<'i>(
+    id: MonkeyID,
+    lines: &mut impl Iterator<Item = Line<'i>>,
+    last_line: &mut usize,
+    throw_targets: &mut Vec<(Line<'i>, &'i str, MonkeyID)>,
+) -> Result<(u64, MonkeyID, MonkeyID), ParseError> {
+    let ((line_number, line), divisible_num) = expect_line(lines, last_line, "Test: divisible by")?;
+    let divisible_num = match parse_number::<u64>(DAY, line_number, line, divisible_num)? {
+        0 => {
+            return Err(ParseError::at(
+                DAY,
+                line_number,
+                line,
+                divisible_num,
+                "expected non-zero divisor",
+            ))
+        }
+        n => n,
+    };
+
+    let mut target = |prefix: &str| {
+        let (line, raw_target) = expect_line(lines, last_line, prefix)?;
+        let target = parse_number::<MonkeyID>(DAY, line.0, line.1, raw_target)?;
+        // a monkey keeping the item would inspect it forever
+        if target == id {
+            return Err(ParseError::at(
+                DAY,
+                line.0,
+                line.1,
+                raw_target,
+                "monkey can't throw to itself",
+            ));
+        }
+        throw_targets.push((line, raw_target, target));
+        Ok::<MonkeyID, ParseError>(target)
+    };
+
+    let true_cond = target("If true: throw to monkey")?;
+    let false_cond = target("If false: throw to monkey")?;
+
+    Ok((divisible_num, true_cond, false_cond))
+}
+
+#[aoc_generator(day11)]
+pub fn parse_input(input: &str) -> Result<Troop, ParseError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let mut last_line = 0;
+    let mut throw_targets = vec![];
+    let mut monkeys = vec![];
+
+    while lines.peek().is_some() {
+        expect_line(&mut lines, &mut last_line, "Monkey")?;
+        let (line, items) = expect_line(&mut lines, &mut last_line, "Starting items:")?;
+        let items = parse_starting_items(line, items)?;
+        let ((line_number, line), expression) =
+            expect_line(&mut lines, &mut last_line, "Operation: new =")?;
+        let operation = Expr::parse(line_number, line, expression)?;
+        let (div, if_true, if_false) = parse_test(
+            monkeys.len(),
+            &mut lines,
+            &mut last_line,
+            &mut throw_targets,
+        )?;
+
+        monkeys.push(Monkey {
+            id: monkeys.len(),
+            items,
+            operation,
+            inspect_count: 0,
+            div,
+            if_true,
+            if_false,
         });
     }
 
-    let mut inspection_counts = input
+    if let Some(((line_number, line), raw_target, _)) = throw_targets
         .iter()
-        .map(|monkey| *monkey.inspect_count.borrow())
-        .collect::<Vec<u64>>();
+        .find(|(_, _, target)| *target >= monkeys.len())
+    {
+        return Err(ParseError::at(
+            DAY,
+            *line_number,
+            line,
+            raw_target,
+            format!("expected monkey id lower than {}", monkeys.len()),
+        ));
+    }
+
+    Ok(Troop { monkeys })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modulo {
+    /// Exact worry levels, they may overflow.
+    None,
+    /// Worry levels modulo the product of all divisors.
+    Product,
+    /// Worry levels modulo the least common multiple of all divisors.
+    Lcm,
+}
+
+/// Rules of a simulation, part 1 and part 2 differ only in these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationConfig {
+    /// Applied to the worry level after every inspection, `old / 3` in part 1.
+    pub relief: Option<Expr>,
+    pub rounds: u64,
+    pub modulo: Modulo,
+    /// How many of the most active monkeys make up the monkey business.
+    pub top: usize,
+}
+
+impl SimulationConfig {
+    pub fn part1() -> Self {
+        SimulationConfig {
+            relief: Some(Expr::Binary(
+                Box::new(Expr::Old),
+                Operator::Div,
+                Box::new(Expr::Constant(3)),
+            )),
+            rounds: 20,
+            modulo: Modulo::None,
+            top: 2,
+        }
+    }
+
+    pub fn part2() -> Self {
+        SimulationConfig {
+            relief: None,
+            rounds: 10000,
+            modulo: Modulo::Product,
+            top: 2,
+        }
+    }
+}
+
+impl<W: Worry> Troop<W> {
+    /// Plays all the configured rounds, worry levels are kept as they are.
+    pub fn simulate(&mut self, config: &SimulationConfig) -> Result<(), WorryError> {
+        for _ in 0..config.rounds {
+            self.round(config)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Monkey business after playing by the config's rules.
+pub fn simulate(input: &Troop, config: &SimulationConfig) -> Result<u64, WorryError> {
+    monkey_business(inspections(input, config, false)?, config.top)
+}
+
+/// Same as [`simulate`], with every item followed on its own in parallel.
+pub fn simulate_parallel(input: &Troop, config: &SimulationConfig) -> Result<u64, WorryError> {
+    monkey_business(inspections(input, config, true)?, config.top)
+}
+
+fn inspections(
+    input: &Troop,
+    config: &SimulationConfig,
+    parallel: bool,
+) -> Result<Vec<u64>, WorryError> {
+    fn run<W: Worry + Send + Sync>(
+        mut troop: Troop<W>,
+        config: &SimulationConfig,
+        parallel: bool,
+    ) -> Result<Vec<u64>, WorryError> {
+        if parallel {
+            return troop.par_inspections(config);
+        }
+
+        troop.simulate(config)?;
+        Ok(troop.inspection_counts())
+    }
+
+    let modulus = match config.modulo {
+        Modulo::None => return run(input.clone(), config, parallel),
+        Modulo::Product => input.modulus()?,
+        Modulo::Lcm => input.lcm()?,
+    };
+    run(
+        input.convert(|item| Modular::new(*item, modulus)),
+        config,
+        parallel,
+    )
+}
+
+#[aoc(day11, part1)]
+pub fn part1(input: &Troop) -> Result<u64, WorryError> {
+    simulate(input, &SimulationConfig::part1())
+}
+
+#[aoc(day11, part1, BigInt)]
+pub fn part1_big(input: &Troop) -> Result<u64, WorryError> {
+    let config = SimulationConfig::part1();
+    let mut troop = input.convert(|item| BigUint::from(*item));
+    troop.simulate(&config)?;
+
+    monkey_business(troop.inspection_counts(), config.top)
+}
+
+#[aoc(day11, part1, Parallel)]
+pub fn part1_parallel(input: &Troop) -> Result<u64, WorryError> {
+    simulate_parallel(input, &SimulationConfig::part1())
+}
+
+#[aoc(day11, part2)]
+pub fn part2(input: &Troop) -> Result<u64, WorryError> {
+    simulate(input, &SimulationConfig::part2())
+}
+
+#[aoc(day11, part2, Parallel)]
+pub fn part2_parallel(input: &Troop) -> Result<u64, WorryError> {
+    simulate_parallel(input, &SimulationConfig::part2())
+}
+
+#[aoc(day11, part2, Cycles)]
+pub fn part2_cycles(input: &Troop) -> Result<u64, WorryError> {
+    let config = SimulationConfig::part2();
+    monkey_business(inspections_after(input, config.rounds)?, config.top)
+}
+
+/// Inspection counts of every monkey after `rounds` rounds of part 2 rules.
+///
+/// Items never interact, so each one is followed on its own. The `(monkey, worry)` state an item
+/// starts a round in is finite once worry is reduced modulo the product of divisors, so its
+/// trajectory must eventually repeat and the remaining rounds are extrapolated from the cycle.
+pub fn inspections_after(input: &Troop, rounds: u64) -> Result<Vec<u64>, WorryError> {
+    let modulo = input.modulus()?;
+    let troop = input.convert(|item| Modular::new(*item, modulo));
+
+    troop
+        .items()
+        .into_par_iter()
+        .map(|(holder, item)| item_inspections(&troop, holder, *item, rounds))
+        .try_reduce(
+            || vec![0; troop.monkeys.len()],
+            |acc, counts| Ok(add(acc, counts)),
+        )
+}
+
+fn item_inspections(
+    troop: &Troop<Modular>,
+    mut holder: MonkeyID,
+    mut worry: Modular,
+    rounds: u64,
+) -> Result<Vec<u64>, WorryError> {
+    // monkeys inspecting the item, round after round, and where each round starts in `visits`
+    let mut visits = vec![];
+    let mut round_starts = vec![];
+    let mut seen = HashMap::new();
+
+    let count = |visits: &[MonkeyID], times: u64, counts: &mut Vec<u64>| {
+        for id in visits {
+            counts[*id] += times;
+        }
+    };
+
+    let mut counts = vec![0; troop.monkeys.len()];
+    for round in 0..rounds {
+        if let Some(start) = seen.insert((holder, worry.value()), round) {
+            let (length, left) = (round - start, rounds - start);
+            let cycle_start = round_starts[start as usize];
+
+            count(&visits[..cycle_start], 1, &mut counts);
+            count(&visits[cycle_start..], left / length, &mut counts);
+            let rest = round_starts
+                .get((start + left % length) as usize)
+                .copied()
+                .unwrap_or(visits.len());
+            count(&visits[cycle_start..rest], 1, &mut counts);
+            return Ok(counts);
+        }
+
+        round_starts.push(visits.len());
+        (holder, worry) = troop.item_round(holder, worry, None, |id| visits.push(id))?;
+    }
 
+    count(&visits, 1, &mut counts);
+    Ok(counts)
+}
+
+fn monkey_business(mut inspection_counts: Vec<u64>, top: usize) -> Result<u64, WorryError> {
     inspection_counts.sort();
-    let last = inspection_counts.len();
-    inspection_counts[last - 1] * inspection_counts[last - 2]
+    inspection_counts
+        .iter()
+        .rev()
+        .take(top)
+        .try_fold(1u64, |acc, count| {
+            acc.checked_mul(*count)
+                .ok_or(WorryError::Overflow(Operator::Mul))
+        })
 }
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap()).unwrap();
+    assert_eq!(10605, got);
+    let got = part1_big(&parse_input(TEST_INPUT).unwrap()).unwrap();
+    assert_eq!(10605, got);
+    let got = part1_parallel(&parse_input(TEST_INPUT).unwrap()).unwrap();
     assert_eq!(10605, got);
 }
 
 #[test]
 pub fn part2_test() {
-    let got = part2(&parse_input(TEST_INPUT));
+    let got = part2(&parse_input(TEST_INPUT).unwrap()).unwrap();
+    assert_eq!(2713310158, got);
+    let got = part2_parallel(&parse_input(TEST_INPUT).unwrap()).unwrap();
     assert_eq!(2713310158, got)
 }
 
+#[test]
+fn round_test() {
+    let mut troop = parse_input(TEST_INPUT).unwrap();
+    troop.round(&SimulationConfig::part1()).unwrap();
+
+    let items = troop
+        .monkeys()
+        .iter()
+        .map(|monkey| monkey.items().iter().copied().collect())
+        .collect::<Vec<Vec<u64>>>();
+    let want: Vec<Vec<u64>> = vec![
+        vec![20, 23, 27, 26],
+        vec![2080, 25, 167, 207, 401, 1046],
+        vec![],
+        vec![],
+    ];
+    assert_eq!(want, items);
+    assert_eq!(vec![2, 4, 3, 5], troop.inspection_counts());
+
+    // following every item on its own ends with the same counts as playing whole rounds
+    let modulo = troop.modulus().unwrap();
+    let mut troop = parse_input(TEST_INPUT)
+        .unwrap()
+        .convert(|item| Modular::new(*item, modulo));
+    let config = SimulationConfig {
+        rounds: 1000,
+        ..SimulationConfig::part2()
+    };
+    let want = troop.par_inspections(&config).unwrap();
+    troop.simulate(&config).unwrap();
+    assert_eq!(want, troop.inspection_counts());
+}
+
+#[test]
+fn simulate_test() {
+    let input = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(Ok(23 * 19 * 13 * 17), input.lcm());
+    let shared = parse_input(&TEST_INPUT.replace("divisible by 23", "divisible by 26")).unwrap();
+    assert_eq!(Ok(26 * 19 * 17), shared.lcm());
+    // shared factors keep the lcm in range after the product overflowed, distinct primes don't
+    let shared = TEST_INPUT
+        .replace("divisible by 23", "divisible by 8589934592")
+        .replace("divisible by 19", "divisible by 4294967296");
+    let shared = parse_input(&shared).unwrap();
+    assert_eq!(Err(WorryError::Overflow(Operator::Mul)), shared.modulus());
+    assert_eq!(Ok((1 << 33) * 13 * 17), shared.lcm());
+    let coprime = TEST_INPUT
+        .replace("divisible by 23", "divisible by 4294967311")
+        .replace("divisible by 19", "divisible by 4294967357");
+    let coprime = parse_input(&coprime).unwrap();
+    assert_eq!(Err(WorryError::Overflow(Operator::Mul)), coprime.lcm());
+
+    let config = SimulationConfig {
+        modulo: Modulo::Lcm,
+        ..SimulationConfig::part2()
+    };
+    assert_eq!(2713310158, simulate(&input, &config).unwrap());
+    assert_eq!(2713310158, simulate_parallel(&input, &config).unwrap());
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Mul)),
+        simulate(&coprime, &config)
+    );
+
+    let config = SimulationConfig {
+        top: 3,
+        ..SimulationConfig::part1()
+    };
+    assert_eq!(105 * 101 * 95, simulate(&input, &config).unwrap());
+
+    // every monkey counts, the product of inspections outgrows u64 with enough rounds
+    let config = SimulationConfig {
+        top: 4,
+        ..SimulationConfig::part2()
+    };
+    assert_eq!(Ok(52166 * 52013 * 47830 * 1938), simulate(&input, &config));
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Mul)),
+        monkey_business(inspections_after(&input, 1_000_000).unwrap(), config.top)
+    );
+
+    // harsher relief and fewer rounds
+    let config = SimulationConfig {
+        relief: Some("old / 4".parse().unwrap()),
+        rounds: 3,
+        ..SimulationConfig::part1()
+    };
+    let mut troop = input.clone();
+    troop.simulate(&config).unwrap();
+    assert_eq!(
+        input.par_inspections(&config).unwrap(),
+        troop.inspection_counts()
+    );
+    assert_ne!(part1(&input).unwrap(), simulate(&input, &config).unwrap());
+
+    // relief can't divide worry levels kept modulo something
+    let config = SimulationConfig {
+        modulo: Modulo::Product,
+        ..SimulationConfig::part1()
+    };
+    assert_eq!(
+        Err(WorryError::Unsupported(Operator::Div)),
+        simulate(&input, &config)
+    );
+}
+
+#[test]
+fn observer_test() {
+    let mut troop = parse_input(TEST_INPUT).unwrap();
+    let mut verbose = Verbose::new();
+    troop
+        .round_with(&SimulationConfig::part1(), &mut verbose)
+        .unwrap();
+
+    let want = "\
+Monkey 0:
+  Monkey inspects an item with a worry level of 79.
+    Worry level is multiplied by 19 to 1501.
+    Monkey gets bored with item. Worry level is divided by 3 to 500.
+    Current worry level is not divisible by 23.
+    Item with worry level 500 is thrown to monkey 3.
+  Monkey inspects an item with a worry level of 98.
+    Worry level is multiplied by 19 to 1862.
+    Monkey gets bored with item. Worry level is divided by 3 to 620.
+    Current worry level is not divisible by 23.
+    Item with worry level 620 is thrown to monkey 3.
+Monkey 1:
+  Monkey inspects an item with a worry level of 54.
+    Worry level increases by 6 to 60.
+    Monkey gets bored with item. Worry level is divided by 3 to 20.
+    Current worry level is not divisible by 19.
+    Item with worry level 20 is thrown to monkey 0.
+";
+    assert!(verbose.text().starts_with(want));
+    assert!(verbose
+        .text()
+        .contains("    Worry level is multiplied by itself to 6241.\n"));
+    assert_eq!(
+        4 + 5 * 14,
+        verbose.text().lines().count(),
+        "a line per turn and five per inspection"
+    );
+
+    let mut troop = parse_input(TEST_INPUT).unwrap();
+    let mut summary = RoundSummary::new(Summary::Items);
+    for _ in 0..2 {
+        troop
+            .round_with(&SimulationConfig::part1(), &mut summary)
+            .unwrap();
+    }
+    let want = "\
+After round 1, the monkeys are holding items with these worry levels:
+Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: 
+Monkey 3: 
+
+After round 2, the monkeys are holding items with these worry levels:
+Monkey 0: 695, 10, 71, 135, 350
+Monkey 1: 43, 49, 58, 55, 362
+Monkey 2: 
+Monkey 3: 
+";
+    assert_eq!(want, summary.text());
+
+    let modulo = troop.modulus().unwrap();
+    let mut troop = parse_input(TEST_INPUT)
+        .unwrap()
+        .convert(|item| Modular::new(*item, modulo));
+    let mut summary = RoundSummary::new(Summary::Inspections).at(&[1, 20]);
+    for _ in 0..20 {
+        troop
+            .round_with(&SimulationConfig::part2(), &mut summary)
+            .unwrap();
+    }
+    let want = "\
+== After round 1 ==
+Monkey 0 inspected items 2 times.
+Monkey 1 inspected items 4 times.
+Monkey 2 inspected items 3 times.
+Monkey 3 inspected items 6 times.
+
+== After round 20 ==
+Monkey 0 inspected items 99 times.
+Monkey 1 inspected items 97 times.
+Monkey 2 inspected items 8 times.
+Monkey 3 inspected items 103 times.
+";
+    assert_eq!(want, summary.text());
+}
+
+#[test]
+fn send_sync_test() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Troop>();
+    assert_send_sync::<Troop<BigUint>>();
+    assert_send_sync::<Troop<Modular>>();
+}
+
+#[test]
+fn inspections_after_test() {
+    let input = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(vec![2, 4, 3, 6], inspections_after(&input, 1).unwrap());
+    assert_eq!(vec![99, 97, 8, 103], inspections_after(&input, 20).unwrap());
+    assert_eq!(
+        vec![5204, 4792, 199, 5192],
+        inspections_after(&input, 1000).unwrap()
+    );
+    assert_eq!(2713310158, part2_cycles(&input).unwrap());
+
+    // every item gets inspected at least once a round
+    let rounds = 1_000_000_000_000;
+    let counts = inspections_after(&input, rounds).unwrap();
+    assert!(counts.iter().sum::<u64>() >= 10 * rounds);
+    assert_eq!(vec![0; 4], inspections_after(&input, 0).unwrap());
+}
+
+#[test]
+pub fn parse_input_error_test() {
+    let input = TEST_INPUT.replace("Test: divisible by 19", "Test: divisible 19");
+    let got = parse_input(&input).err().unwrap();
+    assert_eq!((11, 1), (got.line, got.column));
+    assert_eq!("expected 'Test: divisible by'", got.message);
+
+    let input = TEST_INPUT.replace("new = old + 3", "new = old ^ 3");
+    let got = parse_input(&input).err().unwrap();
+    assert_eq!((24, 22), (got.line, got.column));
+
+    let input = TEST_INPUT.replace("new = old + 3", "new = (old + 3");
+    let got = parse_input(&input).err().unwrap();
+    assert_eq!((24, 26), (got.line, got.column));
+
+    let input = TEST_INPUT.replace("throw to monkey 0", "throw to monkey 4");
+    let got = parse_input(&input).err().unwrap();
+    assert_eq!((13, 29), (got.line, got.column));
+
+    let input = TEST_INPUT.replacen("throw to monkey 2", "throw to monkey 0", 1);
+    let got = parse_input(&input).err().unwrap();
+    assert_eq!((5, 28), (got.line, got.column));
+    assert_eq!("monkey can't throw to itself", got.message);
+
+    let (truncated, _) = TEST_INPUT.rsplit_once('\n').unwrap();
+    let got = parse_input(truncated).err().unwrap();
+    assert_eq!((27, 1), (got.line, got.column));
+}
+
+#[test]
+fn expr_test() {
+    let cases = [
+        ("old * 19", "old * 19", 190),
+        ("old*old", "old * old", 100),
+        ("(old + 3) * 2 - 1", "(old + 3) * 2 - 1", 25),
+        ("old - (4 - 1)", "old - (4 - 1)", 7),
+        ("((old - 4) - 1)", "old - 4 - 1", 5),
+        ("old / 3 % 2", "old / 3 % 2", 1),
+        ("old / (3 % 2)", "old / (3 % 2)", 10),
+        ("2 + 3 * old", "2 + 3 * old", 32),
+    ];
+
+    for (text, canonical, value) in cases {
+        let expr = text.parse::<Expr>().unwrap();
+        assert_eq!(canonical, expr.to_string(), "{text}");
+        assert_eq!(Ok(value), expr.eval(&10u64), "{text}");
+        assert_eq!(expr, canonical.parse::<Expr>().unwrap(), "{text}");
+    }
+}
+
+#[test]
+fn expr_error_test() {
+    let error = |text: &str| {
+        let error = text.parse::<Expr>().unwrap_err();
+        (error.column, error.message)
+    };
+
+    assert_eq!(
+        (6, "expected number, 'old' or '('".to_string()),
+        error("old +")
+    );
+    assert_eq!((9, "expected ')'".to_string()), error("(old + 1"));
+    assert_eq!((10, "expected ')'".to_string()), error("(old + 1 2"));
+    assert_eq!((5, "unexpected token".to_string()), error("old ) + 1"));
+    assert_eq!(
+        (1, "expected number, 'old' or '('".to_string()),
+        error("new")
+    );
+    assert_eq!(
+        (7, "expected number".to_string()),
+        error("old + 99999999999999999999")
+    );
+}
+
+#[test]
+fn worry_test() {
+    assert_eq!(Ok(7), 4u64.apply(Operator::Add, &3));
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Mul)),
+        u64::MAX.apply(Operator::Mul, &2)
+    );
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Sub)),
+        1u64.apply(Operator::Sub, &2)
+    );
+    assert_eq!(
+        Err(WorryError::DivisionByZero),
+        1u64.apply(Operator::Rem, &0)
+    );
+
+    let big = BigUint::from(u64::MAX);
+    assert_eq!(
+        Ok(BigUint::from(u64::MAX as u128 * 2)),
+        big.apply(Operator::Mul, &big.lift(2))
+    );
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Sub)),
+        big.lift(1).apply(Operator::Sub, &big)
+    );
+
+    let modular = Modular::new(9, 7);
+    assert_eq!(2, modular.value());
+    assert_eq!(
+        Ok(Modular::new(4, 7)),
+        modular.apply(Operator::Mul, &modular)
+    );
+    assert_eq!(
+        Ok(Modular::new(6, 7)),
+        modular.apply(Operator::Sub, &modular.lift(3))
+    );
+    assert_eq!(
+        Err(WorryError::Unsupported(Operator::Div)),
+        modular.apply(Operator::Div, &modular)
+    );
+    // no overflow even when the modulus needs all 64 bits
+    let modular = Modular::new(u64::MAX - 1, u64::MAX);
+    assert_eq!(
+        Ok(Modular::new(1, u64::MAX)),
+        modular.apply(Operator::Mul, &modular)
+    );
+}
+
+#[test]
+fn overflow_test() {
+    // the item grows a million times on every inspection, after 20 rounds it has 200+ digits
+    let input = "\
+Monkey 0:
+  Starting items: 1
+  Operation: new = old * 1000000
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items:
+  Operation: new = old * 1000000
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 0";
+
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Mul)),
+        part1(&parse_input(input).unwrap())
+    );
+    assert_eq!(Ok(20 * 20), part1_big(&parse_input(input).unwrap()));
+
+    // worry levels stay small in part 2, but the divisors multiply past u64
+    let input = input
+        .replace("divisible by 2", "divisible by 4294967311")
+        .replace("divisible by 3", "divisible by 4294967357");
+    let input = parse_input(&input).unwrap();
+    assert_eq!(Err(WorryError::Overflow(Operator::Mul)), input.modulus());
+    assert_eq!(Err(WorryError::Overflow(Operator::Mul)), part2(&input));
+    assert_eq!(
+        Err(WorryError::Overflow(Operator::Mul)),
+        part2_cycles(&input)
+    );
+}
+
+#[test]
+fn monkey_display_test() {
+    let troop = parse_input(TEST_INPUT).unwrap();
+    let text = troop
+        .monkeys()
+        .iter()
+        .map(|monkey| monkey.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    let want = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+";
+    assert!(text.starts_with(want));
+
+    let reparsed = parse_input(&text).unwrap();
+    assert_eq!(
+        troop
+            .monkeys()
+            .iter()
+            .map(Monkey::to_string)
+            .collect::<Vec<_>>(),
+        reparsed
+            .monkeys()
+            .iter()
+            .map(Monkey::to_string)
+            .collect::<Vec<_>>()
+    );
+
+    // monkeys may hold no items at all
+    let single = Monkey {
+        id: 0,
+        items: VecDeque::<u64>::new(),
+        operation: "old % 7 + (old - 1) / 2".parse().unwrap(),
+        inspect_count: 0,
+        div: 5,
+        if_true: 1,
+        if_false: 1,
+    };
+    let other = Monkey {
+        id: 1,
+        if_true: 0,
+        if_false: 0,
+        ..single.clone()
+    };
+    let reparsed = parse_input(&format!("{single}\n{other}")).unwrap();
+    assert_eq!(vec![single, other], reparsed.monkeys());
+}
+
 #[cfg(test)]
 const TEST_INPUT: &str = r#"Monkey 0:
 Starting items: 79, 98
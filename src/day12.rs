@@ -1,128 +1,90 @@
+use crate::error::ParseError;
+use crate::grid::Grid;
+use glam::IVec2;
 use rayon::prelude::*;
 
-type Index = usize;
-type Height = u8;
+const DAY: u8 = 12;
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Map {
-    inner: Vec<u8>,
-    width: usize,
-    start: usize,
-    end: usize,
+    grid: Grid<u8>,
+    start: IVec2,
+    end: IVec2,
 }
 
-impl Map {
-    fn left_neighbor(&self, index: usize) -> Option<(Index, Height)> {
-        if index % self.width == 0 {
-            return None;
-        }
-
-        Some((index - 1, self.inner[index - 1]))
-    }
-
-    fn right_neighbor(&self, index: usize) -> Option<(Index, Height)> {
-        if index % self.width == self.width - 1 {
-            return None;
-        }
-
-        Some((index + 1, self.inner[index + 1]))
-    }
-
-    fn top_neighbor(&self, index: usize) -> Option<(Index, Height)> {
-        if index < self.width {
-            return None;
-        }
-
-        Some((index - self.width, self.inner[index - self.width]))
-    }
-
-    fn bottom_neighbor(&self, index: usize) -> Option<(Index, Height)> {
-        if index + self.width >= self.inner.len() {
-            return None;
-        }
-
-        Some((index + self.width, self.inner[index + self.width]))
-    }
-}
-
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Pathfinder {
-    current: usize,
-    //map: &'m Map,
+    current: IVec2,
 }
 
 impl Pathfinder {
     pub fn sucessors(&self, map: &Map) -> Vec<Pathfinder> {
-        fn resolve_succesor(
-            current_height: u8,
-            possible_move: Option<(Index, Height)>,
-            sucessors: &mut Vec<Pathfinder>,
-        ) {
-            if let Some((index, height)) = possible_move {
-                //println!("current_height={current_height} | height={height}");
-                match current_height >= height {
-                    true => sucessors.push(Pathfinder { current: index }),
-
-                    false if (height - current_height) < 2 => {
-                        sucessors.push(Pathfinder { current: index })
-                    }
-                    _ => (),
-                }
-            }
-        }
+        let current_height = *map.grid.get(self.current).expect("pathfinder left the map");
 
-        let current_height = map.inner[self.current];
-        let mut sucessors = vec![];
-        resolve_succesor(
-            current_height,
-            map.left_neighbor(self.current),
-            &mut sucessors,
-        );
-        resolve_succesor(
-            current_height,
-            map.top_neighbor(self.current),
-            &mut sucessors,
-        );
-        resolve_succesor(
-            current_height,
-            map.right_neighbor(self.current),
-            &mut sucessors,
-        );
-        resolve_succesor(
-            current_height,
-            map.bottom_neighbor(self.current),
-            &mut sucessors,
-        );
-        sucessors
+        map.grid
+            .neighbors4(self.current)
+            .filter(|(_, height)| **height <= current_height + 1)
+            .map(|(current, _)| Pathfinder { current })
+            .collect()
     }
 }
 
 #[aoc_generator(day12)]
-pub fn parse_input(input: &str) -> Map {
+pub fn parse_input(input: &str) -> Result<Map, ParseError> {
     let width = input
         .lines()
         .map(|line| line.len())
-        .nth(0)
-        .expect("expected line width, but got nothing");
-    let mut inner = input.replace("\n", "").into_bytes();
+        .next()
+        .filter(|width| *width > 0)
+        .ok_or_else(|| ParseError::missing_line(DAY, 1, "expected at least one row"))?;
+
+    let mut inner = Vec::with_capacity(input.len());
+    for (index, line) in input.lines().enumerate() {
+        if line.len() != width {
+            return Err(ParseError::at(
+                DAY,
+                index + 1,
+                line,
+                line,
+                format!("expected row of width {width}"),
+            ));
+        }
+
+        if let Some((position, c)) = line
+            .char_indices()
+            .find(|(_, c)| !c.is_ascii_lowercase() && *c != 'S' && *c != 'E')
+        {
+            return Err(ParseError::at(
+                DAY,
+                index + 1,
+                line,
+                &line[position..position + c.len_utf8()],
+                "expected height 'a'-'z' or 'S'/'E' marker",
+            ));
+        }
+
+        inner.extend_from_slice(line.as_bytes());
+    }
+
+    let last_line = input.lines().count() + 1;
     let start = inner
         .iter()
         .position(|b| *b == b'S')
-        .expect("no starting position found");
+        .ok_or_else(|| ParseError::missing_line(DAY, last_line, "no starting position found"))?;
     let end = inner
         .iter()
         .position(|b| *b == b'E')
-        .expect("no finish position found");
+        .ok_or_else(|| ParseError::missing_line(DAY, last_line, "no finish position found"))?;
 
     inner[start] = 97;
     inner[end] = 122;
 
-    Map {
-        inner,
-        width,
-        start,
-        end,
-    }
+    let grid = Grid::from_vec(width, inner);
+    Ok(Map {
+        start: grid.position_of(start),
+        end: grid.position_of(end),
+        grid,
+    })
 }
 
 #[aoc(day12, part1)]
@@ -147,12 +109,11 @@ pub fn part1(input: &Map) -> i32 {
 #[aoc(day12, part2)]
 pub fn part2(input: &Map) -> usize {
     let starts = input
-        .inner
+        .grid
         .iter()
-        .enumerate()
         .filter(|(_, v)| **v == b'a')
-        .map(|(index, _)| index)
-        .collect::<Vec<usize>>();
+        .map(|(pos, _)| pos)
+        .collect::<Vec<IVec2>>();
 
     starts
         .par_iter()
@@ -172,11 +133,19 @@ pub fn part2(input: &Map) -> usize {
 
 #[test]
 fn parse_input_test() {
-    parse_input(
+    let got = parse_input(
         r#"abcccccaaaaaacccaaaccaaaaaaaacccaaaaaaccccccccccccccccccccccccccccaaaaaaaaaaaaaacacccccccccccccccccccccccccccccccaaaaaaaacccccccccccccccccccccccccccccccccccccccccccccaaaaa
     abcccccaaaaaaaacaaaaccaaaaaaccccaaaaaaccccccccccaaacccccccccccccccaaaaaaaaaaaaaaaacccccccccccccccccccccccccccccccaaaaaaaaaccccccaaaccccccccccccccccccccccccccccccccccaaaaaa
     abccccaaaaaaaaacaaaaccaaaaaaccccaaaaaaaaccccccccaaaccccccccccccccccaaaaaaaaaaaaaaccccaaaccccccccccccccccccccccccccaaaaaaaaccccacaaaccccccccccccccccaaccccccccccccccccaaaaaa"#,
-    );
+    )
+    .unwrap_err();
+    assert_eq!((2, 1), (got.line, got.column));
+
+    let got = parse_input("Sab\nabc").unwrap_err();
+    assert_eq!("no finish position found", got.message);
+
+    let got = parse_input("Sab\naEc\na1c").unwrap_err();
+    assert_eq!((3, 2), (got.line, got.column));
 }
 
 #[cfg(test)]
@@ -188,87 +157,75 @@ abdefghi"#;
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(31, got)
 }
 
 #[test]
 pub fn part2_test() {
-    let got = part2(&parse_input(TEST_INPUT));
+    let got = part2(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(29, got)
 }
 
-#[test]
-fn test_neighbors() {
-    let map = Map {
-        inner: vec![
-            //0  1  3  4  5
-            0, 1, 2, 3, 4, // 1
-            5, 6, 7, 8, 9, // 2
-            10, 11, 12, 13, 14, // 3
-            15, 16, 17, 18, 19, // 4
-        ],
-        width: 5,
-        start: 0,
-        end: 0,
-    };
-
-    assert_eq!(2, map.top_neighbor(7).unwrap().1);
-    assert_eq!(None, map.top_neighbor(2));
-
-    assert_eq!(None, map.left_neighbor(5));
-    assert_eq!(5, map.left_neighbor(6).unwrap().1);
-
-    assert_eq!(6, map.right_neighbor(5).unwrap().1);
-    assert_eq!(None, map.right_neighbor(9));
-
-    assert_eq!(12, map.bottom_neighbor(7).unwrap().1);
-    assert_eq!(None, map.bottom_neighbor(17));
-}
-
 #[test]
 fn possible_neighbors_test() {
     // all neighbors around 7 should return
     let map = Map {
-        inner: vec![
-            //0  1  3  4  5
-            0, 1, 1, 3, 4, // 1
-            5, 1, 7, 1, 9, // 2
-            10, 11, 1, 13, 14, // 3
-            15, 16, 13, 18, 19, // 4
-        ],
-        width: 5,
-        start: 0,
-        end: 0,
+        grid: Grid::from_vec(
+            5,
+            vec![
+                //0  1  3  4  5
+                0, 1, 1, 3, 4, // 1
+                5, 1, 7, 1, 9, // 2
+                10, 11, 1, 13, 14, // 3
+                15, 16, 13, 18, 19, // 4
+            ],
+        ),
+        start: IVec2::ZERO,
+        end: IVec2::ZERO,
     };
 
-    let pathfinder = Pathfinder { current: 7 };
+    let pathfinder = Pathfinder {
+        current: IVec2::new(2, 1),
+    };
 
     let got = pathfinder.sucessors(&map);
     let want = vec![
-        Pathfinder { current: 6 },
-        Pathfinder { current: 2 },
-        Pathfinder { current: 8 },
-        Pathfinder { current: 12 },
+        Pathfinder {
+            current: IVec2::new(1, 1),
+        },
+        Pathfinder {
+            current: IVec2::new(2, 0),
+        },
+        Pathfinder {
+            current: IVec2::new(3, 1),
+        },
+        Pathfinder {
+            current: IVec2::new(2, 2),
+        },
     ];
 
     assert_eq!(want, got);
 
     // no neighbor arount 7 should return
     let map = Map {
-        inner: vec![
-            //0  1  3  4  5
-            0, 1, 10, 3, 4, // 1
-            5, 10, 7, 10, 9, // 2
-            10, 11, 10, 13, 14, // 3
-            15, 16, 13, 18, 19, // 4
-        ],
-        width: 5,
-        start: 0,
-        end: 0,
+        grid: Grid::from_vec(
+            5,
+            vec![
+                //0  1  3  4  5
+                0, 1, 10, 3, 4, // 1
+                5, 10, 7, 10, 9, // 2
+                10, 11, 10, 13, 14, // 3
+                15, 16, 13, 18, 19, // 4
+            ],
+        ),
+        start: IVec2::ZERO,
+        end: IVec2::ZERO,
     };
 
-    let pathfinder = Pathfinder { current: 7 };
+    let pathfinder = Pathfinder {
+        current: IVec2::new(2, 1),
+    };
 
     let got = pathfinder.sucessors(&map);
     let want: Vec<Pathfinder> = vec![];
@@ -276,44 +233,60 @@ fn possible_neighbors_test() {
 
     // all neighbor around 7 should return
     let map = Map {
-        inner: vec![
-            //0  1  3  4  5
-            0, 1, 8, 3, 4, // 1
-            5, 8, 7, 8, 9, // 2
-            10, 11, 8, 13, 14, // 3
-            15, 16, 13, 18, 19, // 4
-        ],
-        width: 5,
-        start: 0,
-        end: 0,
+        grid: Grid::from_vec(
+            5,
+            vec![
+                //0  1  3  4  5
+                0, 1, 8, 3, 4, // 1
+                5, 8, 7, 8, 9, // 2
+                10, 11, 8, 13, 14, // 3
+                15, 16, 13, 18, 19, // 4
+            ],
+        ),
+        start: IVec2::ZERO,
+        end: IVec2::ZERO,
     };
 
-    let pathfinder = Pathfinder { current: 7 };
+    let pathfinder = Pathfinder {
+        current: IVec2::new(2, 1),
+    };
 
     let got = pathfinder.sucessors(&map);
     let want = vec![
-        Pathfinder { current: 6 },
-        Pathfinder { current: 2 },
-        Pathfinder { current: 8 },
-        Pathfinder { current: 12 },
+        Pathfinder {
+            current: IVec2::new(1, 1),
+        },
+        Pathfinder {
+            current: IVec2::new(2, 0),
+        },
+        Pathfinder {
+            current: IVec2::new(3, 1),
+        },
+        Pathfinder {
+            current: IVec2::new(2, 2),
+        },
     ];
     assert_eq!(want, got);
 
     // no neighbor around 7 should return
     let map = Map {
-        inner: vec![
-            //0  1  3  4  5
-            0, 1, 9, 3, 4, // 1
-            5, 9, 7, 9, 9, // 2
-            10, 11, 9, 13, 14, // 3
-            15, 16, 13, 18, 19, // 4
-        ],
-        width: 5,
-        start: 0,
-        end: 0,
+        grid: Grid::from_vec(
+            5,
+            vec![
+                //0  1  3  4  5
+                0, 1, 9, 3, 4, // 1
+                5, 9, 7, 9, 9, // 2
+                10, 11, 9, 13, 14, // 3
+                15, 16, 13, 18, 19, // 4
+            ],
+        ),
+        start: IVec2::ZERO,
+        end: IVec2::ZERO,
     };
 
-    let pathfinder = Pathfinder { current: 7 };
+    let pathfinder = Pathfinder {
+        current: IVec2::new(2, 1),
+    };
 
     let got = pathfinder.sucessors(&map);
     let want: Vec<Pathfinder> = vec![];
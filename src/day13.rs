@@ -1,50 +1,50 @@
-use core::panic;
-use std::{cmp::Ordering, iter::Peekable, slice::Iter};
+use crate::error::ParseError;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    slice,
+    str::FromStr,
+};
+#[cfg(feature = "json")]
+use {
+    serde_json::Value as Json,
+    std::io::{self, BufRead, BufWriter, Write},
+};
 
-const BYTE_OFFSET: u8 = 48;
-const LINE_FEED: u8 = 10;
+const DAY: u8 = 13;
 
-#[derive(Debug)]
-pub enum CompareResult {
-    Smaller,
-    Equal,
-    Bigger,
-    InvalidComparison,
-}
-
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// A packet or one of its elements.
+///
+/// Equality, ordering and hashing all follow the packet rules, an integer is equal to the list
+/// holding just that integer, so `[[1],2]` and `[1,[2]]` are the same packet.
+#[derive(Debug, Clone)]
 pub enum Type {
-    Value(u8),
+    Value(i128),
     Array(Vec<Self>),
 }
 
 impl Type {
-    pub fn parse_value((tens, ones): (u8, u8)) -> Self {
-        let n = ((tens - BYTE_OFFSET) * 10) + (ones - BYTE_OFFSET);
-        Self::Value(n)
-    }
-
-    pub fn parse_array(iter: &mut Peekable<Iter<'_, u8>>) -> Self {
-        let mut v = vec![];
-        while let Some(c) = iter.next() {
-            match *c {
-                b',' | b' ' => (),
-                b']' | LINE_FEED => return Self::Array(v),
-                b'[' => v.push(Self::parse_array(iter)),
-                _ => v.push(Self::parse_value(bytes_to_ones_and_tens(
-                    *c,
-                    **iter.peek().unwrap(),
-                ))),
-            }
+    /// Parses a single packet, `line_number` is only used for error reporting.
+    pub fn parse(line_number: usize, line: &str) -> Result<Self, ParseError> {
+        let mut parser = PacketParser {
+            line_number,
+            line,
+            position: 0,
+        };
+
+        parser.skip_whitespace();
+        if parser.peek() != Some(b'[') {
+            return Err(parser.error("expected '['"));
         }
-        unreachable!("found unclosed array!")
-    }
 
-    pub fn unwrap(self) -> Vec<Self> {
-        match self {
-            Self::Array(v) => v,
-            n => vec![n],
+        let packet = parser.value()?;
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected input after packet end"));
         }
+
+        Ok(packet)
     }
 
     pub fn to_vec(&self) -> Self {
@@ -53,135 +53,814 @@ impl Type {
             Self::Value(v) => Self::Array(vec![Self::Value(*v)]),
         }
     }
+}
+
+impl Ord for Type {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // slices compare element by element and then by length, just like packets do
+        match (self, other) {
+            (Self::Value(left), Self::Value(right)) => left.cmp(right),
+            (Self::Array(left), Self::Array(right)) => left.as_slice().cmp(right),
+            (Self::Value(_), Self::Array(right)) => slice::from_ref(self).cmp(right),
+            (Self::Array(left), Self::Value(_)) => left.as_slice().cmp(slice::from_ref(other)),
+        }
+    }
+}
+
+impl PartialOrd for Type {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Type {}
+
+impl Hash for Type {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // lists of a single element are equal to that element, so they must hash the same
+        match self {
+            Self::Array(v) if v.len() == 1 => v[0].hash(state),
+            Self::Value(v) => {
+                state.write_u8(0);
+                v.hash(state);
+            }
+            Self::Array(v) => {
+                state.write_u8(1);
+                state.write_usize(v.len());
+                v.iter().for_each(|element| element.hash(state));
+            }
+        }
+    }
+}
+
+/// Canonical packet syntax, `[1,[2,3],[]]`.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(v) => write!(f, "{v}"),
+            Self::Array(v) => {
+                write!(f, "[")?;
+                for (index, element) in v.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Type::parse(1, s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
 
-    fn compare_values(&self, rhs: &Type) -> CompareResult {
-        let (Self::Value(a), Self::Value(b)) = (self, rhs) else {panic!("can compare only values")};
+/// What decided the order of two packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Both sides had an integer, this side's was smaller.
+    Smaller(Side),
+    /// This side's list ran out of items first.
+    RanOut(Side),
+}
+
+/// Why two packets are, or aren't, in the right order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Indices leading to the deciding element. When a list ran out, the last one is the index of
+    /// the missing item.
+    pub path: Vec<usize>,
+    /// Integers converted to a list on the way, by the number of indices leading to them.
+    pub promotions: Vec<(usize, Side)>,
+    /// `None` when the packets are equal.
+    pub reason: Option<Reason>,
+    walkthrough: String,
+}
 
-        match a.cmp(b) {
-            Ordering::Equal => CompareResult::Equal,
-            Ordering::Greater => CompareResult::Bigger,
-            Ordering::Less => CompareResult::Smaller,
+impl Explanation {
+    pub fn ordering(&self) -> Ordering {
+        match self.reason {
+            None => Ordering::Equal,
+            Some(Reason::Smaller(Side::Left) | Reason::RanOut(Side::Left)) => Ordering::Less,
+            Some(Reason::Smaller(Side::Right) | Reason::RanOut(Side::Right)) => Ordering::Greater,
         }
     }
 
-    fn compare_lists(&self, rhs: &Type) -> CompareResult {
-        let (Self::Array(left_vec), Self::Array(right_vec)) = (self, rhs) else {
-            panic!("passed values are not lists");
+    fn line(&mut self, depth: usize, line: impl fmt::Display) {
+        self.walkthrough += &format!("{:indent$}- {line}\n", "", indent = depth * 2);
+    }
+
+    fn decide(&mut self, depth: usize, reason: Reason) -> Ordering {
+        let line = match reason {
+            Reason::Smaller(side) => format!("{} side is smaller", capitalize(side)),
+            Reason::RanOut(side) => format!("{} side ran out of items", capitalize(side)),
         };
+        self.reason = Some(reason);
+        let ordering = self.ordering();
+        let verdict = if ordering.is_lt() { "" } else { "not " };
+        self.line(
+            depth,
+            format!("{line}, so inputs are {verdict}in the right order"),
+        );
 
-        let len = left_vec.len().max(right_vec.len());
+        ordering
+    }
 
-        for index in 0..len {
-            let left = left_vec.get(index);
-            let right = right_vec.get(index);
+    fn compare(&mut self, depth: usize, left: &Type, right: &Type) -> Ordering {
+        self.line(depth, format!("Compare {left} vs {right}"));
 
-            if left.is_none() && right.is_some() {
-                return CompareResult::Smaller;
-            }
+        match (left, right) {
+            (Type::Value(a), Type::Value(b)) => match a.cmp(b) {
+                Ordering::Less => self.decide(depth + 1, Reason::Smaller(Side::Left)),
+                Ordering::Greater => self.decide(depth + 1, Reason::Smaller(Side::Right)),
+                Ordering::Equal => Ordering::Equal,
+            },
+            (Type::Array(a), Type::Array(b)) => {
+                for (index, (left, right)) in a.iter().zip(b).enumerate() {
+                    self.path.push(index);
+                    let ordering = self.compare(depth + 1, left, right);
+                    if ordering.is_ne() {
+                        return ordering;
+                    }
+                    self.path.pop();
+                }
 
-            if left.is_some() && right.is_none() {
-                return CompareResult::Bigger;
+                let side = match a.len().cmp(&b.len()) {
+                    Ordering::Less => Side::Left,
+                    Ordering::Greater => Side::Right,
+                    Ordering::Equal => return Ordering::Equal,
+                };
+                self.path.push(a.len().min(b.len()));
+                self.decide(depth + 1, Reason::RanOut(side))
             }
+            (Type::Value(_), Type::Array(_)) => {
+                self.promote(depth + 1, Side::Left, left);
+                self.compare(depth + 1, &left.to_vec(), right)
+            }
+            (Type::Array(_), Type::Value(_)) => {
+                self.promote(depth + 1, Side::Right, right);
+                self.compare(depth + 1, left, &right.to_vec())
+            }
+        }
+    }
+
+    fn promote(&mut self, depth: usize, side: Side, value: &Type) {
+        self.promotions.push((self.path.len(), side));
+        self.line(
+            depth,
+            format!("Mixed types; convert {side} to [{value}] and retry comparison"),
+        );
+    }
+}
+
+fn capitalize(side: Side) -> &'static str {
+    match side {
+        Side::Left => "Left",
+        Side::Right => "Right",
+    }
+}
+
+/// The comparison step by step, the way the puzzle walks through its example.
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.walkthrough)
+    }
+}
+
+/// Explains how `left` compares to `right`.
+pub fn explain(left: &Type, right: &Type) -> Explanation {
+    let mut explanation = Explanation {
+        path: vec![],
+        promotions: vec![],
+        reason: None,
+        walkthrough: String::new(),
+    };
+    explanation.compare(0, left, right);
+
+    explanation
+}
+
+/// Walkthrough of every pair, `== Pair 1 ==` and so on.
+pub fn walkthrough(input: &[(Type, Type)]) -> String {
+    input
+        .iter()
+        .enumerate()
+        .map(|(index, (left, right))| format!("== Pair {} ==\n{}", index + 1, explain(left, right)))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+struct PacketParser<'i> {
+    line_number: usize,
+    line: &'i str,
+    position: usize,
+}
+
+impl<'i> PacketParser<'i> {
+    fn peek(&self) -> Option<u8> {
+        self.line.as_bytes().get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        self.error_at(self.position, message)
+    }
+
+    // points at the character starting at `position`, past the end of line when there's none
+    fn error_at(&self, position: usize, message: &str) -> ParseError {
+        let Some(c) = self.line[position..].chars().next() else {
+            return ParseError::end_of_line(DAY, self.line_number, self.line, message);
+        };
+        let snippet = &self.line[position..position + c.len_utf8()];
+        ParseError::at(DAY, self.line_number, self.line, snippet, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
 
-            if left.is_none() && right.is_none() {
-                return CompareResult::Equal;
+    fn value(&mut self) -> Result<Type, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'[') => {
+                self.position += 1;
+                self.list()
             }
+            Some(b'-' | b'0'..=b'9') => self.integer(),
+            _ => Err(self.error("expected integer or '['")),
+        }
+    }
 
-            let left = left.unwrap();
-            let right = right.unwrap();
+    // everything after the opening bracket, the closing one included
+    fn list(&mut self) -> Result<Type, ParseError> {
+        let mut values = vec![];
 
-            match (left, right) {
-                (Self::Value(_), Self::Value(_)) => match left.compare_values(right) {
-                    CompareResult::Equal => continue,
-                    o => {
-                        return o;
-                    }
-                },
-                (Self::Array(_), Self::Array(_)) => match Self::compare_lists(left, right) {
-                    CompareResult::Equal => continue,
-                    o => {
-                        return o;
-                    }
-                },
-                (Self::Value(_), Self::Array(_)) => {
-                    match Self::compare_lists(&left.to_vec(), right) {
-                        CompareResult::Equal => continue,
-                        o => {
-                            return o;
-                        }
-                    }
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Type::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Type::Array(values));
                 }
+                None => return Err(self.error("expected ']'")),
+                Some(_) => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Type, ParseError> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+
+        let digits = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if self.position == digits {
+            return Err(self.error("expected digit"));
+        }
+
+        self.line[start..self.position]
+            .parse()
+            .map(Type::Value)
+            .map_err(|_| {
+                let snippet = &self.line[start..self.position];
+                ParseError::at(
+                    DAY,
+                    self.line_number,
+                    self.line,
+                    snippet,
+                    "number out of range",
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'p> {
+    Open,
+    Close,
+    // sign and digits without leading zeros, zero has no digits at all
+    Integer(bool, &'p [u8]),
+    End,
+}
+
+// Walks a raw packet token by token. Closing brackets of integers promoted to a list are virtual,
+// they come right after the integer without being in the packet.
+struct Cursor<'p> {
+    packet: &'p [u8],
+    position: usize,
+    wraps: usize,
+    virtual_closes: usize,
+}
+
+impl<'p> Cursor<'p> {
+    fn new(packet: &'p [u8]) -> Self {
+        Cursor {
+            packet,
+            position: 0,
+            wraps: 0,
+            virtual_closes: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .packet
+            .get(self.position)
+            .is_some_and(|c| *c == b',' || c.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    // the next token and the position right after it
+    fn peek(&mut self) -> (Token<'p>, usize) {
+        if self.virtual_closes > 0 {
+            return (Token::Close, self.position);
+        }
+
+        self.skip_separators();
+        let start = self.position;
+        match self.packet.get(start) {
+            None => (Token::End, start),
+            Some(b'[') => (Token::Open, start + 1),
+            Some(b']') => (Token::Close, start + 1),
+            Some(_) => {
+                let negative = self.packet[start] == b'-';
+                let digits = start + negative as usize;
+                let end = self.packet[digits..]
+                    .iter()
+                    .position(|c| !c.is_ascii_digit())
+                    .map_or(self.packet.len(), |length| digits + length);
+                let zeros = self.packet[digits..end]
+                    .iter()
+                    .take_while(|c| **c == b'0')
+                    .count();
+                let magnitude = &self.packet[digits + zeros..end];
+                // anything unexpected is skipped as an integer, so malformed input still ends
+                let end = end.max(start + 1);
+
+                (
+                    Token::Integer(negative && !magnitude.is_empty(), magnitude),
+                    end,
+                )
+            }
+        }
+    }
+
+    fn advance(&mut self, (token, end): (Token<'p>, usize)) {
+        if self.virtual_closes > 0 {
+            self.virtual_closes -= 1;
+            return;
+        }
+
+        self.position = end;
+        if let Token::Integer(..) = token {
+            self.virtual_closes = std::mem::take(&mut self.wraps);
+        }
+    }
+
+    // the integer up next is read as a list holding just that integer
+    fn wrap(&mut self) {
+        self.wraps += 1;
+    }
+}
+
+fn compare_integers(
+    (left_negative, left): (bool, &[u8]),
+    (right_negative, right): (bool, &[u8]),
+) -> Ordering {
+    let magnitude = left.len().cmp(&right.len()).then_with(|| left.cmp(right));
+    match (left_negative, right_negative) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    }
+}
+
+/// Compares two packets straight from their text, without parsing them or allocating.
+///
+/// Integers are compared by their digits, so they can have any size. Packets are expected to be
+/// well formed, malformed ones get some ordering but never a panic.
+pub fn compare_raw(left: &[u8], right: &[u8]) -> Ordering {
+    let (mut left, mut right) = (Cursor::new(left), Cursor::new(right));
 
-                (Self::Array(_), Self::Value(_)) => {
-                    match Self::compare_lists(left, &right.to_vec()) {
-                        CompareResult::Equal => continue,
-                        o => {
-                            return o;
-                        }
+    loop {
+        let (left_token, right_token) = (left.peek(), right.peek());
+        match (left_token.0, right_token.0) {
+            (Token::End, Token::End) => return Ordering::Equal,
+            (Token::Open, Token::Open) | (Token::Close, Token::Close) => {
+                left.advance(left_token);
+                right.advance(right_token);
+            }
+            (Token::Integer(left_negative, a), Token::Integer(right_negative, b)) => {
+                match compare_integers((left_negative, a), (right_negative, b)) {
+                    Ordering::Equal => {
+                        left.advance(left_token);
+                        right.advance(right_token);
                     }
+                    ordering => return ordering,
                 }
             }
+            (Token::Open, Token::Integer(..)) => {
+                left.advance(left_token);
+                right.wrap();
+            }
+            (Token::Integer(..), Token::Open) => {
+                left.wrap();
+                right.advance(right_token);
+            }
+            (Token::Close | Token::End, _) => return Ordering::Less,
+            (_, Token::Close | Token::End) => return Ordering::Greater,
         }
+    }
+}
+
+fn raw_packets(input: &str) -> impl Iterator<Item = &[u8]> {
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::as_bytes)
+}
 
-        CompareResult::Equal
+/// [`part1`] comparing the packets as they are written.
+pub fn part1_raw(input: &str) -> usize {
+    let packets = raw_packets(input).collect::<Vec<&[u8]>>();
+    packets
+        .chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| pair.len() == 2 && compare_raw(pair[0], pair[1]).is_lt())
+        .map(|(index, _)| index + 1)
+        .sum()
+}
+
+/// [`part2`] comparing the packets as they are written. There's no need to sort them, the
+/// position of a divider is just the number of packets before it.
+pub fn part2_raw(input: &str) -> usize {
+    let (two, six): (&[u8], &[u8]) = (b"[[2]]", b"[[6]]");
+    let (mut index_two, mut index_six) = (1, 2);
+    for packet in raw_packets(input) {
+        index_two += compare_raw(packet, two).is_lt() as usize;
+        index_six += compare_raw(packet, six).is_lt() as usize;
     }
 
-    pub fn get_first_number(&self, depth: &mut u8) -> u8 {
+    index_two * index_six
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    /// The varint starting at this offset doesn't fit 128 bits.
+    Overflow(usize),
+    /// The packet ended before this offset.
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Array(v) => {
-                if v.is_empty() {
-                    return 0;
-                }
-                *depth += 1;
-                v[0].get_first_number(depth)
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of packet"),
+            DecodeError::Overflow(offset) => write!(f, "varint at offset {offset} overflows"),
+            DecodeError::TrailingBytes(offset) => {
+                write!(f, "unexpected bytes after packet end at offset {offset}")
             }
-            Self::Value(v) => *v,
         }
     }
 }
 
-fn bytes_to_ones_and_tens(first: u8, second: u8) -> (u8, u8) {
-    if second >= 48 && second <= 57 {
-        return (first, second);
+impl std::error::Error for DecodeError {}
+
+// Varint whose lowest bit tells lists from integers. The first byte holds the kind and six bits of
+// the value, every following one seven more, the highest bit says whether another byte follows.
+fn write_header(out: &mut Vec<u8>, list: bool, value: u128) {
+    let mut byte = list as u8 | ((value as u8 & 0x3f) << 1);
+    let mut rest = value >> 6;
+    while rest > 0 {
+        out.push(byte | 0x80);
+        byte = rest as u8 & 0x7f;
+        rest >>= 7;
     }
+    out.push(byte);
+}
+
+fn read_header(bytes: &[u8], position: &mut usize) -> Result<(bool, u128), DecodeError> {
+    let start = *position;
+    let mut next = || {
+        let byte = *bytes.get(*position).ok_or(DecodeError::UnexpectedEnd)?;
+        *position += 1;
+        Ok(byte)
+    };
 
-    (b'0', first)
+    let first = next()?;
+    let mut value = (first as u128 >> 1) & 0x3f;
+    let (mut more, mut shift) = (first & 0x80 != 0, 6);
+    while more {
+        let byte = next()?;
+        let bits = (byte & 0x7f) as u128;
+        if shift >= 128 || (bits << shift) >> shift != bits {
+            return Err(DecodeError::Overflow(start));
+        }
+        value |= bits << shift;
+        (more, shift) = (byte & 0x80 != 0, shift + 7);
+    }
+
+    Ok((first & 1 == 1, value))
 }
 
-impl From<&str> for Type {
-    fn from(line: &str) -> Self {
-        let mut iter = line.as_bytes().iter().peekable();
-        let mut vec = vec![];
+// Big endian bytes without the leading zeros, prefixed by their count so that shorter numbers
+// come first. Negative numbers count down from the same middle point with their bits flipped.
+fn write_ordered_integer(out: &mut Vec<u8>, value: i128) {
+    let (prefix, magnitude, flip) = if value < 0 {
+        (0x7f, !value as u128, 0xff)
+    } else {
+        (0x80, value as u128, 0)
+    };
+
+    let bytes = magnitude.to_be_bytes();
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    let length = (bytes.len() - zeros) as u8;
+    out.push(if value < 0 {
+        prefix - length
+    } else {
+        prefix + length
+    });
+    out.extend(bytes[zeros..].iter().map(|byte| byte ^ flip));
+}
+
+const KEY_END: u8 = 0;
+const KEY_BOUNDARY: u8 = 1;
+const KEY_EMPTY: u8 = 1;
+const KEY_INTEGER: u8 = 2;
+
+impl Type {
+    /// Compact binary form, lists are prefixed by their length and integers are zigzag varints.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.encode_into(&mut out);
+        out
+    }
+
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Value(v) => write_header(out, false, ((v << 1) ^ (v >> 127)) as u128),
+            Self::Array(items) => {
+                write_header(out, true, items.len() as u128);
+                items.iter().for_each(|item| item.encode_into(out));
+            }
+        }
+    }
 
-        while let Some(c) = iter.next() {
-            match c {
-                b'[' => {
-                    return Self::parse_array(&mut iter);
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        fn decode_at(bytes: &[u8], position: &mut usize) -> Result<Type, DecodeError> {
+            match read_header(bytes, position)? {
+                (false, zigzag) => Ok(Type::Value((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))),
+                (true, length) => {
+                    // every item takes at least a byte, so there's no trusting bigger lengths
+                    let mut items =
+                        Vec::with_capacity((length as usize).min(bytes.len() - *position));
+                    for _ in 0..length {
+                        items.push(decode_at(bytes, position)?);
+                    }
+                    Ok(Type::Array(items))
                 }
-                b',' | b' ' => (),
-                b']' | &LINE_FEED | 3 => {
-                    return Self::Array(vec);
+            }
+        }
+
+        let mut position = 0;
+        let packet = decode_at(bytes, &mut position)?;
+        if position < bytes.len() {
+            return Err(DecodeError::TrailingBytes(position));
+        }
+
+        Ok(packet)
+    }
+
+    /// Bytes which compare like the packets do, equal packets get equal keys.
+    ///
+    /// Lists holding a single integer are that integer, anything else is flattened into its
+    /// leaves, the integers and the empty lists, with a boundary between two leaves saying how
+    /// deep the next item starts. A list running out first has its boundary at a shallower depth.
+    pub fn key(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.key_into(0, &mut out);
+        out.push(KEY_END);
+        out
+    }
+
+    // `depth` counts the lists holding this element, the packet itself is at zero
+    fn key_into(&self, depth: usize, out: &mut Vec<u8>) {
+        match (self.as_integer(), self) {
+            (Some(value), _) => {
+                out.push(KEY_INTEGER);
+                write_ordered_integer(out, value);
+            }
+            (None, Self::Array(items)) if items.is_empty() => {
+                out.push(KEY_EMPTY);
+                write_ordered_integer(out, depth as i128);
+            }
+            (None, Self::Array(items)) => {
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        out.push(KEY_BOUNDARY);
+                        write_ordered_integer(out, depth as i128 + 1);
+                    }
+                    item.key_into(depth + 1, out);
                 }
-                v => vec.push(Self::parse_value(bytes_to_ones_and_tens(
-                    *v,
-                    **iter.peek().unwrap_or(&&0),
-                ))),
             }
+            (None, Self::Value(_)) => unreachable!("integers are always read as such"),
         }
+    }
 
-        Self::Array(vec)
+    // an integer, possibly wrapped in lists of a single item, which makes no difference
+    fn as_integer(&self) -> Option<i128> {
+        match self {
+            Self::Value(v) => Some(*v),
+            Self::Array(items) if items.len() == 1 => items[0].as_integer(),
+            Self::Array(_) => None,
+        }
     }
 }
 
+/// JSON can't hold the packet, only integers fitting 64 bits survive the conversion.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    OutOfRange(i128),
+    /// Valid JSON which isn't a packet, like strings, floats or objects.
+    NotAPacket(String),
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::OutOfRange(value) => write!(f, "integer {value} doesn't fit 64 bits"),
+            JsonError::NotAPacket(found) => write!(f, "expected integer or array, found {found}"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::error::Error for JsonError {}
+
+#[cfg(feature = "json")]
+impl TryFrom<&Type> for Json {
+    type Error = JsonError;
+
+    fn try_from(packet: &Type) -> Result<Self, Self::Error> {
+        match packet {
+            Type::Value(value) => i64::try_from(*value)
+                .map(Json::from)
+                .or_else(|_| u64::try_from(*value).map(Json::from))
+                .map_err(|_| JsonError::OutOfRange(*value)),
+            Type::Array(v) => v.iter().map(Json::try_from).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<&Json> for Type {
+    type Error = JsonError;
+
+    fn try_from(json: &Json) -> Result<Self, Self::Error> {
+        let integer = |json: &Json| {
+            json.as_i64()
+                .map(i128::from)
+                .or_else(|| json.as_u64().map(i128::from))
+        };
+
+        match json {
+            Json::Array(v) => v
+                .iter()
+                .map(Type::try_from)
+                .collect::<Result<_, _>>()
+                .map(Type::Array),
+            Json::Number(_) => integer(json)
+                .map(Type::Value)
+                .ok_or_else(|| JsonError::NotAPacket(json.to_string())),
+            _ => Err(JsonError::NotAPacket(json.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn parse_json_line(line_number: usize, line: &str) -> Result<Type, ParseError> {
+    let json = serde_json::from_str::<Json>(line).map_err(|error| {
+        // serde_json counts columns in bytes, step back to the start of the character
+        let mut position = error.column().saturating_sub(1).min(line.len());
+        while !line.is_char_boundary(position) {
+            position -= 1;
+        }
+        let end = line[position..]
+            .chars()
+            .next()
+            .map_or(position, |c| position + c.len_utf8());
+        ParseError::at(
+            DAY,
+            line_number,
+            line,
+            &line[position..end],
+            error.to_string(),
+        )
+    })?;
+
+    Type::try_from(&json)
+        .map_err(|error| ParseError::at(DAY, line_number, line, line.trim(), error.to_string()))
+}
+
+/// Reads one JSON packet per line, empty lines are skipped, and writes them back sorted in
+/// compact JSON, one per line. Returns the number of packets. Lines which aren't packets fail
+/// with [`io::ErrorKind::InvalidData`] holding the [`ParseError`].
+#[cfg(feature = "json")]
+pub fn sort_json_lines(input: impl BufRead, out: impl Write) -> io::Result<usize> {
+    let invalid = |error: Box<dyn std::error::Error + Send + Sync>| {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    };
+
+    let mut packets = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            packets.push(parse_json_line(index + 1, &line).map_err(|error| invalid(error.into()))?);
+        }
+    }
+
+    packets.sort();
+
+    let mut out = BufWriter::new(out);
+    for packet in &packets {
+        let json = Json::try_from(packet).map_err(|error| invalid(error.into()))?;
+        serde_json::to_writer(&mut out, &json)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+
+    Ok(packets.len())
+}
+
 #[aoc_generator(day13)]
-fn parse_input(input: &str) -> Vec<(Type, Type)> {
-    input
-        .split("\n\n")
-        .map(|split| {
-            let (first, second) = split.split_once("\n").unwrap();
-            (Type::from(first), Type::from(second))
-        })
-        .collect::<Vec<(Type, Type)>>()
+fn parse_input(input: &str) -> Result<Vec<(Type, Type)>, ParseError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let mut pairs = vec![];
+
+    while let Some((index, first)) = lines.next() {
+        let Some((second_index, second)) = lines.next() else {
+            return Err(ParseError::missing_line(
+                DAY,
+                index + 2,
+                "expected second packet of the pair",
+            ));
+        };
+
+        pairs.push((
+            Type::parse(index + 1, first)?,
+            Type::parse(second_index + 1, second)?,
+        ));
+    }
+
+    Ok(pairs)
 }
 
 #[aoc(day13, part1)]
@@ -189,17 +868,14 @@ pub fn part1(input: &[(Type, Type)]) -> usize {
     input
         .iter()
         .enumerate()
-        .map(|(index, pair)| (index + 1, pair.0.compare_lists(&pair.1)))
-        .filter_map(|(index, ordering)| match ordering {
-            CompareResult::Smaller => Some(index),
-            _ => None,
-        })
+        .filter(|(_, (left, right))| left < right)
+        .map(|(index, _)| index + 1)
         .sum()
 }
 
 #[aoc(day13, part2)]
 pub fn part2(input: &[(Type, Type)]) -> usize {
-    let (mut data, mut data2): (Vec<Type>, Vec<Type>) = input.to_vec().into_iter().unzip();
+    let (mut data, mut data2): (Vec<Type>, Vec<Type>) = input.iter().cloned().unzip();
 
     data.append(&mut data2);
 
@@ -209,12 +885,7 @@ pub fn part2(input: &[(Type, Type)]) -> usize {
     data.push(two.clone());
     data.push(six.clone());
 
-    data.sort_by(|a, b| match a.compare_lists(b) {
-        CompareResult::Bigger => Ordering::Greater,
-        CompareResult::Smaller => Ordering::Less,
-        CompareResult::Equal => Ordering::Equal,
-        _ => unreachable!(),
-    });
+    data.sort();
 
     let index_two = data.iter().position(|t| t == &two);
     let index_six = data.iter().position(|t| t == &six);
@@ -249,78 +920,523 @@ const TEST_INPUT: &str = r#"[1,1,3,1,1]
 
 #[test]
 fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(13, got)
 }
 
 #[test]
 fn part2_test() {
-    let got = part2(&parse_input(TEST_INPUT));
+    let got = part2(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(140, got)
 }
 
 #[test]
-fn bytes_to_ones_and_tens_tes() {
-    let got = bytes_to_ones_and_tens(b'1', b',');
-    assert_eq!((b'0', b'1'), got);
+fn parse_input_error_test() {
+    let got = parse_input("[1,[2]\n[3]").unwrap_err();
+    assert_eq!((1, 7), (got.line, got.column));
+    assert_eq!("expected ']'", got.message);
+
+    let got = parse_input("[1]\n[3]]").unwrap_err();
+    assert_eq!((2, 4), (got.line, got.column));
 
-    let got = bytes_to_ones_and_tens(b'1', b'0');
-    assert_eq!((b'1', b'0'), got);
+    let got = parse_input("[1]\n[3,1e5]").unwrap_err();
+    assert_eq!((2, 5), (got.line, got.column));
 
-    let got = bytes_to_ones_and_tens(b'9', b']');
-    assert_eq!((b'0', b'9'), got);
+    let got = parse_input("[1]\n[3]\n\n[a]\n[]").unwrap_err();
+    assert_eq!((4, 2), (got.line, got.column));
 
-    let got = bytes_to_ones_and_tens(b'5', LINE_FEED);
-    assert_eq!((b'0', b'5'), got);
+    let got = parse_input("[1]\n[3]\n\n[]").unwrap_err();
+    assert_eq!((5, 1), (got.line, got.column));
 }
 
 #[test]
-fn parse_array_test() {
-    let mut iter = "1, 2, 3]".as_bytes().iter().peekable();
-    let got = Type::parse_array(&mut iter);
+fn parse_test() {
+    // compared through the canonical text, `==` would also accept a different nesting
+    let parse = |line: &str| Type::parse(1, line).map(|packet| packet.to_string());
+
     assert_eq!(
-        Type::Array(vec![Type::Value(1), Type::Value(2), Type::Value(3),]),
-        got
+        Ok(Type::Array(vec![Type::Value(1), Type::Value(2), Type::Value(3)]).to_string()),
+        parse("[1, 2, 3]")
+    );
+    assert_eq!(
+        Ok(Type::Array(vec![
+            Type::Array(vec![Type::Value(1)]),
+            Type::Array(vec![
+                Type::Value(2),
+                Type::Array(vec![Type::Value(3), Type::Value(4), Type::Value(5)]),
+                Type::Value(6)
+            ])
+        ])
+        .to_string()),
+        parse("[[1], [2, [3, 4, 5], 6]]")
+    );
+    assert_eq!(
+        Ok(Type::Array(vec![Type::Array(vec![Type::Value(7), Type::Array(vec![])])]).to_string()),
+        parse("  [ [7 ,[ ] ] ]\t")
+    );
+    assert_eq!(
+        Ok(Type::Array(vec![
+            Type::Value(-12),
+            Type::Value(18_446_744_073_709_551_616),
+            Type::Value(i128::MIN)
+        ])
+        .to_string()),
+        parse("[-12,18446744073709551616,-170141183460469231731687303715884105728]")
     );
 }
 
 #[test]
-fn from_str_for_type_test() {
-    let s = "[1, 2, 3]";
-    let got = Type::from(s);
+fn parse_error_test() {
+    let error = |line: &str| {
+        let error = Type::parse(1, line).unwrap_err();
+        (error.column, error.message)
+    };
+
+    assert_eq!((1, "expected '['".to_string()), error("1"));
+    assert_eq!((1, "expected '['".to_string()), error(""));
+    assert_eq!((4, "expected integer or '['".to_string()), error("[1,]"));
+    assert_eq!((2, "expected integer or '['".to_string()), error("[,1]"));
+    assert_eq!((4, "expected ',' or ']'".to_string()), error("[1 2]"));
+    assert_eq!((3, "expected digit".to_string()), error("[-]"));
+    assert_eq!((6, "expected ']'".to_string()), error("[[1] "));
     assert_eq!(
-        Type::Array(vec![Type::Value(1), Type::Value(2), Type::Value(3),]),
-        got
+        (5, "unexpected input after packet end".to_string()),
+        error("[1] x")
     );
-
-    let s = "[[1],[2,3,4]]";
-    let got = Type::from(s);
     assert_eq!(
-        Type::Array(vec![
-            Type::Array(vec!(Type::Value(1))),
-            Type::Array(vec![Type::Value(2), Type::Value(3), Type::Value(4)])
-        ]),
-        got
+        (2, "number out of range".to_string()),
+        error("[170141183460469231731687303715884105728]")
     );
+}
 
-    let s = "[[1], [2, [3, 4, 5], 6]]";
-    let got = Type::from(s);
-    assert_eq!(
-        Type::Array(vec![
-            Type::Array(vec!(Type::Value(1))),
+#[test]
+fn compare_raw_test() {
+    assert_eq!(13, part1_raw(TEST_INPUT));
+    assert_eq!(140, part2_raw(TEST_INPUT));
+
+    let cases = [
+        ("[1,1,3,1,1]", "[1,1,5,1,1]", Ordering::Less),
+        ("[[1],[2,3,4]]", "[[1],4]", Ordering::Less),
+        ("[9]", "[[8,7,6]]", Ordering::Greater),
+        ("[[[]]]", "[[]]", Ordering::Greater),
+        ("[[1],2]", "[1,[2]]", Ordering::Equal),
+        ("[[[[3]]],1]", "[3,[1]]", Ordering::Equal),
+        ("[[[[3]]],1]", "[[3,0]]", Ordering::Less),
+        ("[ 10 , [ ] ]", "[10,[]]", Ordering::Equal),
+        ("[007]", "[7]", Ordering::Equal),
+        ("[-0]", "[0]", Ordering::Equal),
+        ("[-5]", "[-12]", Ordering::Greater),
+        ("[-1]", "[0]", Ordering::Less),
+        (
+            "[99999999999999999999999999999999999999999]",
+            "[1]",
+            Ordering::Greater,
+        ),
+    ];
+    for (left, right, want) in cases {
+        assert_eq!(
+            want,
+            compare_raw(left.as_bytes(), right.as_bytes()),
+            "{left} vs {right}"
+        );
+        assert_eq!(
+            want.reverse(),
+            compare_raw(right.as_bytes(), left.as_bytes()),
+            "{right} vs {left}"
+        );
+    }
+
+    // garbage gets some ordering without panicking
+    for (left, right) in [("[1,", "[1]"), ("]]", "[["), ("[a]", "[-]"), ("", "[]")] {
+        compare_raw(left.as_bytes(), right.as_bytes());
+    }
+}
+
+#[cfg(test)]
+fn random_packets(count: usize, integers: impl Fn(u64) -> i128) -> Vec<Type> {
+    // shallow lists with lots of empty and single item ones, which is where promotion gets
+    // interesting
+    fn packet(next: &mut impl FnMut() -> u64, integers: &impl Fn(u64) -> i128, depth: u32) -> Type {
+        if depth > 0 && !next().is_multiple_of(3) {
+            let length = next() % 4;
+            Type::Array(
+                (0..length)
+                    .map(|_| packet(next, integers, depth - 1))
+                    .collect(),
+            )
+        } else {
+            Type::Value(integers(next()))
+        }
+    }
+
+    let mut next = crate::random::xorshift();
+    (0..count)
+        .map(|_| {
             Type::Array(vec![
-                Type::Value(2),
-                Type::Array(vec![Type::Value(3), Type::Value(4), Type::Value(5)]),
-                Type::Value(6)
+                packet(&mut next, &integers, 4),
+                packet(&mut next, &integers, 3),
             ])
-        ]),
-        got
+        })
+        .collect()
+}
+
+#[test]
+fn compare_raw_random_test() {
+    // small integers, so that packets often share a prefix
+    let packets = random_packets(4000, |random| random as i128 % 4 - 1);
+    for pair in packets.chunks(2) {
+        let (left_text, right_text) = (pair[0].to_string(), pair[1].to_string());
+        assert_eq!(
+            pair[0].cmp(&pair[1]),
+            compare_raw(left_text.as_bytes(), right_text.as_bytes()),
+            "{left_text} vs {right_text}"
+        );
+    }
+}
+
+#[test]
+fn explain_test() {
+    let pairs = parse_input(TEST_INPUT).unwrap();
+    let explanations = pairs
+        .iter()
+        .map(|(left, right)| explain(left, right))
+        .collect::<Vec<Explanation>>();
+
+    let paths = explanations
+        .iter()
+        .map(|explanation| explanation.path.clone())
+        .collect::<Vec<_>>();
+    let want: Vec<Vec<usize>> = vec![
+        vec![2],
+        vec![1, 0],
+        vec![0, 0],
+        vec![3],
+        vec![3],
+        vec![0],
+        vec![0, 0],
+        vec![1, 1, 1, 1, 2],
+    ];
+    assert_eq!(want, paths);
+
+    use Side::{Left, Right};
+    let reasons = explanations
+        .iter()
+        .map(|explanation| explanation.reason.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            Reason::Smaller(Left),
+            Reason::Smaller(Left),
+            Reason::Smaller(Right),
+            Reason::RanOut(Left),
+            Reason::RanOut(Right),
+            Reason::RanOut(Left),
+            Reason::RanOut(Right),
+            Reason::Smaller(Right),
+        ],
+        reasons
     );
+    assert_eq!(vec![(1, Right)], explanations[1].promotions);
+    assert_eq!(vec![(1, Left)], explanations[2].promotions);
+    for ((left, right), explanation) in pairs.iter().zip(&explanations) {
+        assert_eq!(left.cmp(right), explanation.ordering());
+    }
+
+    let equal = explain(&"[[1],2]".parse().unwrap(), &"[1,[2]]".parse().unwrap());
+    assert_eq!((None, Ordering::Equal), (equal.reason, equal.ordering()));
+
+    let want = "\
+== Pair 1 ==
+- Compare [1,1,3,1,1] vs [1,1,5,1,1]
+  - Compare 1 vs 1
+  - Compare 1 vs 1
+  - Compare 3 vs 5
+    - Left side is smaller, so inputs are in the right order
+
+== Pair 2 ==
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+
+== Pair 3 ==
+- Compare [9] vs [[8,7,6]]
+  - Compare 9 vs [8,7,6]
+    - Mixed types; convert left to [9] and retry comparison
+    - Compare [9] vs [8,7,6]
+      - Compare 9 vs 8
+        - Right side is smaller, so inputs are not in the right order
 
-    let s = "[[7,[]]]";
-    let got = Type::from(s);
+== Pair 4 ==
+- Compare [[4,4],4,4] vs [[4,4],4,4,4]
+  - Compare [4,4] vs [4,4]
+    - Compare 4 vs 4
+    - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Left side ran out of items, so inputs are in the right order
+
+== Pair 5 ==
+- Compare [7,7,7,7] vs [7,7,7]
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Right side ran out of items, so inputs are not in the right order
+
+== Pair 6 ==
+- Compare [] vs [3]
+  - Left side ran out of items, so inputs are in the right order
+
+== Pair 7 ==
+- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+
+== Pair 8 ==
+- Compare [1,[2,[3,[4,[5,6,7]]]],8,9] vs [1,[2,[3,[4,[5,6,0]]]],8,9]
+  - Compare 1 vs 1
+  - Compare [2,[3,[4,[5,6,7]]]] vs [2,[3,[4,[5,6,0]]]]
+    - Compare 2 vs 2
+    - Compare [3,[4,[5,6,7]]] vs [3,[4,[5,6,0]]]
+      - Compare 3 vs 3
+      - Compare [4,[5,6,7]] vs [4,[5,6,0]]
+        - Compare 4 vs 4
+        - Compare [5,6,7] vs [5,6,0]
+          - Compare 5 vs 5
+          - Compare 6 vs 6
+          - Compare 7 vs 0
+            - Right side is smaller, so inputs are not in the right order
+";
+    assert_eq!(want, walkthrough(&pairs));
+}
+
+#[test]
+fn ord_test() {
+    let packet = |s: &str| s.parse::<Type>().unwrap();
+
+    let pairs = parse_input(TEST_INPUT).unwrap();
+    let orderings = pairs
+        .iter()
+        .map(|(left, right)| left.cmp(right))
+        .collect::<Vec<Ordering>>();
+    use Ordering::{Greater, Less};
     assert_eq!(
-        Type::Array(vec![Type::Array(vec![Type::Value(7), Type::Array(vec![])])]),
-        got
+        vec![Less, Less, Greater, Less, Greater, Less, Greater, Greater],
+        orderings
     );
+
+    assert_eq!(Ordering::Equal, packet("[[1],2]").cmp(&packet("[1,[2]]")));
+    assert_eq!(packet("[[[]]]"), packet("[[[]]]"));
+    assert_ne!(packet("[[]]"), packet("[]"));
+    assert!(packet("[-1]") < packet("[0]"));
+    assert!(packet("[18446744073709551616]") > packet("[18446744073709551615]"));
+    assert_eq!(Ordering::Less, Type::Value(1).cmp(&packet("[1,1]")));
+    assert_eq!(Ordering::Greater, packet("[[2]]").cmp(&Type::Value(1)));
+}
+
+#[test]
+fn display_test() {
+    for text in ["[]", "[1,[2,[3,[4,[5,6,7]]]],8,9]", "[[[]],-4,[]]"] {
+        assert_eq!(text, text.parse::<Type>().unwrap().to_string());
+    }
+    assert_eq!(
+        "[1,[2,3]]",
+        " [ 1 , [2,  3]]".parse::<Type>().unwrap().to_string()
+    );
+    assert_eq!("7", Type::Value(7).to_string());
+}
+
+#[test]
+fn hash_test() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let packets = ["[1,2]", "[[1],2]", "[1,[[2]]]", "[1]", "1", "[[]]", "[]"]
+        .iter()
+        .map(|text| match text.parse() {
+            Ok(packet) => packet,
+            Err(_) => Type::Value(text.parse().unwrap()),
+        })
+        .collect::<Vec<Type>>();
+
+    // `insert` keeps the element already in the set, `collect` doesn't promise that
+    let (mut hashed, mut sorted) = (HashSet::new(), BTreeSet::new());
+    for packet in &packets {
+        hashed.insert(packet.clone());
+        sorted.insert(packet.clone());
+    }
+    assert_eq!(4, hashed.len());
+    assert_eq!(4, sorted.len());
+    assert!(hashed.iter().all(|packet| sorted.contains(packet)));
+    // equal packets are deduplicated whichever way they were written, the first one stays
+    let kept = sorted
+        .iter()
+        .map(|packet| packet.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(vec!["[]", "[[]]", "[1]", "[1,2]"], kept);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_test() {
+    let packet = "[1,[-2,[]],18446744073709551615]".parse::<Type>().unwrap();
+    let json = Json::try_from(&packet).unwrap();
+    assert_eq!(
+        serde_json::json!([1, [-2, []], 18446744073709551615u64]),
+        json
+    );
+    assert_eq!(
+        Ok(packet.to_string()),
+        Type::try_from(&json).map(|packet| packet.to_string())
+    );
+
+    let too_big = Type::Array(vec![Type::Value(-1 << 64)]);
+    assert_eq!(
+        Err(JsonError::OutOfRange(-1 << 64)),
+        Json::try_from(&too_big)
+    );
+    for json in [r#"[1,"2"]"#, "[1.5]", r#"{"a":[]}"#, "null"] {
+        let json = serde_json::from_str::<Json>(json).unwrap();
+        assert!(Type::try_from(&json).is_err(), "{json}");
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn sort_json_lines_test() {
+    let input = "[[2]]\n\n[1, [2, 3]]\n[ ]\n[[1],4]\n";
+    let mut out = vec![];
+    assert_eq!(4, sort_json_lines(input.as_bytes(), &mut out).unwrap());
+    assert_eq!(
+        "[]\n[1,[2,3]]\n[[1],4]\n[[2]]\n",
+        String::from_utf8(out).unwrap()
+    );
+
+    let error = |input: &str| {
+        let error = sort_json_lines(input.as_bytes(), vec![]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        let error = error
+            .into_inner()
+            .unwrap()
+            .downcast::<ParseError>()
+            .unwrap();
+        (error.line, error.column)
+    };
+    assert_eq!((2, 4), error("[1]\n[2,,3]"));
+    assert_eq!((3, 1), error("[1]\n\n[\"a\"]"));
+    // columns count characters, not the bytes serde_json reports
+    assert_eq!((1, 7), error("[\"éé\",,1]"));
+}
+
+#[test]
+fn encode_test() {
+    let packet = |text: &str| text.parse::<Type>().unwrap();
+
+    assert_eq!(vec![0x01], packet("[]").encode());
+    assert_eq!(vec![0x07, 0x04, 0x08, 0x0c], packet("[1,2,3]").encode());
+    assert_eq!(
+        vec![0x07, 0x01, 0x03, 0x7e, 0x80, 0x01],
+        packet("[[],[-32],32]").encode()
+    );
+    assert_eq!(vec![0x80, 0x01], Type::Value(32).encode());
+
+    let extremes = Type::Array(vec![Type::Value(i128::MIN), Type::Value(i128::MAX)]);
+    let encoded = extremes.encode();
+    assert_eq!(1 + 2 * 19, encoded.len());
+    assert_eq!(
+        extremes.to_string(),
+        Type::decode(&encoded).unwrap().to_string()
+    );
+
+    assert_eq!(Err(DecodeError::UnexpectedEnd), Type::decode(&[]));
+    assert_eq!(Err(DecodeError::UnexpectedEnd), Type::decode(&[0x07, 0x02]));
+    assert_eq!(Err(DecodeError::UnexpectedEnd), Type::decode(&[0x81]));
+    assert_eq!(
+        Err(DecodeError::TrailingBytes(1)),
+        Type::decode(&[0x01, 0x01])
+    );
+    assert_eq!(
+        Err(DecodeError::Overflow(1)),
+        Type::decode(&[
+            0x03, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0x7f
+        ])
+    );
+    // a huge length doesn't allocate up front
+    assert_eq!(
+        Err(DecodeError::UnexpectedEnd),
+        Type::decode(&[0xff, 0xff, 0xff, 0xff, 0x0f, 0x02])
+    );
+}
+
+#[test]
+fn encode_random_test() {
+    let packets = random_packets(500, |random| {
+        (random as i128).wrapping_mul(random as i128 - (1 << 63))
+    });
+    for packet in packets {
+        let encoded = packet.encode();
+        assert!(encoded.len() <= packet.to_string().len() + 17, "{packet}");
+        let decoded = Type::decode(&encoded).unwrap();
+        assert_eq!(packet.to_string(), decoded.to_string());
+    }
+}
+
+#[test]
+fn key_test() {
+    let key = |text: &str| text.parse::<Type>().unwrap().key();
+
+    assert_eq!(key("[[1],2]"), key("[1,[[2]]]"));
+    assert_eq!(key("[[[5]]]"), Type::Value(5).key());
+    assert!(key("[]") < key("[[]]"));
+    assert!(key("[[]]") < key("[[[]]]"));
+    assert!(key("[[1,2]]") > key("[1,2]"));
+    assert!(key("[1,[2,3]]") < key("[[1,2],3]"));
+    assert!(key("[-1]") < key("[0]"));
+    assert!(key("[-300]") < key("[-2]"));
+    assert!(key("[255]") < key("[256]"));
+
+    for (left, right) in parse_input(TEST_INPUT).unwrap() {
+        assert_eq!(
+            left.cmp(&right),
+            left.key().cmp(&right.key()),
+            "{left} vs {right}"
+        );
+    }
+}
+
+#[test]
+fn key_random_test() {
+    let packets = random_packets(300, |random| random as i128 % 3 - 1);
+    let keys = packets.iter().map(Type::key).collect::<Vec<Vec<u8>>>();
+
+    for (left, left_key) in packets.iter().zip(&keys) {
+        for (right, right_key) in packets.iter().zip(&keys) {
+            assert_eq!(
+                left.cmp(right),
+                left_key.cmp(right_key),
+                "{left} vs {right}"
+            );
+        }
+    }
+
+    // integers of every size order right too
+    let integers = random_packets(300, |random| {
+        let random = random as i128;
+        random.wrapping_mul(random) >> (random & 0x7f) ^ -(random & 1)
+    });
+    let mut by_key = integers.clone();
+    by_key.sort_by_key(Type::key);
+    let mut sorted = integers;
+    sorted.sort();
+    let text = |packets: Vec<Type>| {
+        packets
+            .iter()
+            .map(|packet| packet.to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(text(sorted), text(by_key));
 }
@@ -1,56 +1,226 @@
+use crate::error::{parse_number, ParseError};
+use crate::grid::{SparseGrid, DOWN, LEFT, RIGHT};
+use crate::image::{Image, ImageFormat, Rgb};
 use glam::IVec2;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const DAY: u8 = 14;
+
+const AIR: u8 = b'.';
+const ROCK: u8 = b'#';
+const SAND: u8 = b'o';
+
+const SOURCE: IVec2 = IVec2::new(500, 0);
+
+const FALL_ORDER: [IVec2; 3] = [DOWN, IVec2::new(-1, 1), IVec2::new(1, 1)];
+
+const AIR_COLOR: Rgb = [24, 24, 32];
+const ROCK_COLOR: Rgb = [128, 128, 128];
+const SAND_COLOR: Rgb = [230, 190, 100];
+const FALLING_COLOR: Rgb = [255, 80, 40];
+const FLOOR_COLOR: Rgb = [96, 64, 48];
+
+/// How the sand enters the cave and moves through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandRules {
+    /// Offsets a grain tries in order, it moves to the first one with air. Every offset has to
+    /// point downwards, otherwise a grain could move forever without settling.
+    pub fall_order: Vec<IVec2>,
+    /// Grains are poured from each source in turn, duplicates are ignored.
+    pub sources: Vec<IVec2>,
+    /// A grain which can't fall may roll up to this many cells sideways, left first,
+    /// onto the nearest cell from which it can fall again.
+    pub spread: Option<i32>,
+}
 
-struct Cave {
-    inner: Vec<[u8; 600]>,
+impl Default for SandRules {
+    fn default() -> Self {
+        SandRules {
+            fall_order: FALL_ORDER.to_vec(),
+            sources: vec![SOURCE],
+            spread: None,
+        }
+    }
 }
 
-impl Cave {
-    pub fn new(stones: &[Vec<IVec2>], depth: usize) -> Self {
-        let mut inner = Vec::with_capacity(50);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesError {
+    NoSources,
+    /// the fall offset doesn't point downwards
+    NotFalling(IVec2),
+}
 
-        for _ in 0..=(depth + 1) {
-            inner.push([46u8; 600])
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::NoSources => write!(f, "sand needs at least one source"),
+            RulesError::NotFalling(offset) => {
+                write!(
+                    f,
+                    "fall offset {},{} doesn't point down",
+                    offset.x, offset.y
+                )
+            }
         }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+enum Grain {
+    Rested(IVec2),
+    Abyss,
+    Blocked,
+}
+
+struct Cave {
+    grid: SparseGrid<u8>,
+    depth: i32,
+    floor: Option<i32>,
+    rules: SandRules,
+    // cells the last grain from each source fell through, the next grain follows the same way
+    paths: Vec<Vec<IVec2>>,
+    next_source: usize,
+}
+
+impl Cave {
+    pub fn new(stones: &[Vec<IVec2>], depth: i32) -> Self {
+        let mut grid = SparseGrid::sparse(AIR);
 
         for stone_coords in stones {
             let mut coords_iter = stone_coords.iter().peekable();
 
             while let Some(start) = coords_iter.next() {
-                let Some(end) = coords_iter.peek() else {continue;};
-                //println!("-----------------------------------------------------------------------------------------------------------------------------------------");
-                add_stones(&mut inner, *start, **end);
-
-                /* inner.iter().for_each(|line| {
-                    line.iter()
-                        .skip(430)
-                        .map(|b| char::from(*b))
-                        .for_each(|c| print!("{c}"));
-                    println!("");
-                });
-                println!("-----------------------------------------------------------------------------------------------------------------------------------------"); */
+                let Some(end) = coords_iter.peek() else {
+                    continue;
+                };
+                add_stones(&mut grid, *start, **end);
             }
         }
 
-        Cave { inner }
+        Cave {
+            grid,
+            depth,
+            floor: None,
+            rules: SandRules::default(),
+            paths: vec![vec![SOURCE]],
+            next_source: 0,
+        }
+    }
+
+    pub fn with_rules(mut self, mut rules: SandRules) -> Result<Self, RulesError> {
+        if let Some(offset) = rules.fall_order.iter().find(|offset| offset.y <= 0) {
+            return Err(RulesError::NotFalling(*offset));
+        }
+        let mut seen = vec![];
+        rules.sources.retain(|source| {
+            let duplicate = seen.contains(source);
+            seen.push(*source);
+            !duplicate
+        });
+        if rules.sources.is_empty() {
+            return Err(RulesError::NoSources);
+        }
+
+        self.paths = rules.sources.iter().map(|source| vec![*source]).collect();
+        self.next_source = 0;
+        self.rules = rules;
+        Ok(self)
     }
 
-    pub fn add_floor(mut self) -> Self {
-        self.inner.push([46u8; 600]);
-        self.inner.push([35; 600]);
+    // floor is infinite and lies two rows below the deepest rock
+    pub fn with_floor(mut self) -> Self {
+        self.floor = Some(self.depth + 2);
         self
     }
 
-    fn simulate_grain_of_sand(&mut self, origin: IVec2, check_for_abyss: bool) -> Option<IVec2> {
-        //println!("simulatin falling grain, origin={origin}");
-        // if we are about to fall into abyss, return None
-        if check_for_abyss {
-            if origin.y >= self.inner.len() as i32 - 1 {
-                return None;
+    /// Drops a grain from the next source which isn't blocked and leaves it resting in the cave.
+    /// Returns `None` once the grain falls into the abyss or every source is blocked.
+    fn pour_grain(&mut self, check_for_abyss: bool) -> Option<IVec2> {
+        for _ in 0..self.paths.len() {
+            let index = self.next_source;
+            self.next_source = (index + 1) % self.paths.len();
+
+            let mut path = std::mem::take(&mut self.paths[index]);
+            let grain = self.pour_grain_along(&mut path, check_for_abyss);
+            self.paths[index] = path;
+
+            match grain {
+                Grain::Rested(pos) => return Some(pos),
+                Grain::Abyss => return None,
+                Grain::Blocked => continue,
+            }
+        }
+
+        None
+    }
+
+    fn pour_grain_along(&mut self, path: &mut Vec<IVec2>, check_for_abyss: bool) -> Grain {
+        // sand from other sources may have landed on the cached path
+        let valid = path.iter().take_while(|pos| self.is_air(**pos)).count();
+        path.truncate(valid);
+        // rolling sideways depends on cells further away than the next one, so the cached
+        // decisions may go stale and the grain has to start over
+        if self.rules.spread.is_some() {
+            path.truncate(1);
+        }
+
+        loop {
+            let Some(current) = path.last().copied() else {
+                return Grain::Blocked;
+            };
+
+            if check_for_abyss && current.y > self.depth {
+                return Grain::Abyss;
+            }
+
+            match self.next_cell(current) {
+                Some(next) => path.push(next),
+                None => {
+                    // grain rests here, the next one continues from its parent cell
+                    path.pop();
+                    self.grid.set(current, SAND);
+                    return Grain::Rested(current);
+                }
             }
         }
+    }
+
+    fn next_cell(&self, current: IVec2) -> Option<IVec2> {
+        let can_fall_from = |pos: IVec2| {
+            self.rules
+                .fall_order
+                .iter()
+                .map(|offset| pos + *offset)
+                .find(|next| self.is_air(*next))
+        };
+
+        if let Some(next) = can_fall_from(current) {
+            return Some(next);
+        }
 
-        if origin.x <= 1 || origin.x >= 599 {
-            return Some(origin);
+        let spread = self.rules.spread.unwrap_or(0);
+        let mut open = [true; 2];
+        for distance in 1..=spread {
+            for (direction, open) in [LEFT, RIGHT].into_iter().zip(open.iter_mut()) {
+                let next = current + direction * distance;
+                *open = *open && self.is_air(next);
+                if *open && can_fall_from(next).is_some() {
+                    return Some(next);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn simulate_grain_of_sand(&mut self, origin: IVec2, check_for_abyss: bool) -> Option<IVec2> {
+        // if we are about to fall into abyss, return None
+        if check_for_abyss && origin.y > self.depth {
+            return None;
         }
 
         if self.can_fall(origin) {
@@ -72,157 +242,283 @@ impl Cave {
         Some(origin)
     }
 
+    // With the floor present the resting sand fills the triangle under the source, except for
+    // cells which are rock or which can't be reached from any of the three cells above them.
+    fn count_sand_rows(&self) -> usize {
+        let floor = self.floor.expect("counting sand rows needs a floor");
+        let offset = SOURCE.x - floor;
+        let mut row = vec![false; 2 * floor as usize + 1];
+        row[(SOURCE.x - offset) as usize] = true;
+        let mut counter = 1;
+
+        for y in 1..floor {
+            let previous = row.clone();
+            for (i, cell) in row.iter_mut().enumerate() {
+                let above = &previous[i.saturating_sub(1)..(i + 2).min(previous.len())];
+                let pos = IVec2::new(i as i32 + offset, y);
+                *cell = above.iter().any(|sand| *sand) && self.grid.get(pos) != Some(&ROCK);
+            }
+            counter += row.iter().filter(|sand| **sand).count();
+        }
+
+        counter
+    }
+
+    fn is_air(&self, pos: IVec2) -> bool {
+        match self.floor {
+            Some(floor) if pos.y >= floor => false,
+            _ => self.grid.get(pos) == Some(&AIR),
+        }
+    }
+
     fn can_fall(&self, current_pos: IVec2) -> bool {
-        self.inner[(current_pos.y + 1) as usize][current_pos.x as usize] == 46
+        self.is_air(current_pos + DOWN)
     }
     fn can_fall_right(&self, current_pos: IVec2) -> bool {
-        self.inner[(current_pos.y + 1) as usize][current_pos.x as usize + 1] == 46
+        self.is_air(current_pos + DOWN + RIGHT)
     }
     fn can_fall_left(&self, current_pos: IVec2) -> bool {
-        self.inner[(current_pos.y + 1) as usize][current_pos.x as usize - 1] == 46
+        self.is_air(current_pos + DOWN + LEFT)
     }
 
-    fn print(&self) {
-        self.inner.iter().for_each(|line| {
-            line.iter()
-                .skip(430)
-                .map(|b| char::from(*b))
-                .for_each(|c| print!("{c}"));
-            println!("")
-        });
-        //println!("{:?}", &self.inner.iter().skip(400).map(|b| char::from(b)));
-    }
-}
+    /// Draws the cave cropped to the rock, the sand, the sources and the floor.
+    /// The cells the next grains fall through are highlighted.
+    fn to_image(&self) -> Image {
+        let (mut min, mut max) = self.grid.bounds();
+        for source in &self.rules.sources {
+            min = min.min(*source);
+            max = max.max(*source);
+        }
 
-fn add_stones(cave: &mut Vec<[u8; 600]>, start: IVec2, end: IVec2) {
-    //println!("start={start}, end={end}");
-    let diff = start - end;
-    //println!("diff={diff}");
+        let falling = self
+            .paths
+            .iter()
+            .filter_map(|path| path.last().copied())
+            .collect::<Vec<_>>();
+        for falling in &falling {
+            min = min.min(*falling);
+            max = max.max(*falling);
+        }
+        if let Some(floor) = self.floor {
+            max.y = max.y.max(floor);
+        }
 
-    if start.x != end.x {
-        let mut current = if !diff.x.is_negative() { end } else { start };
+        let size = max - min + IVec2::ONE;
+        let mut image = Image::new(size.x as usize, size.y as usize, AIR_COLOR);
+        for (pos, cell) in self.grid.iter() {
+            let color = match *cell {
+                ROCK => ROCK_COLOR,
+                SAND => SAND_COLOR,
+                _ => continue,
+            };
+            let pixel = pos - min;
+            image.set(pixel.x as usize, pixel.y as usize, color);
+        }
 
-        //println!("current={current}");
-        for _ in 0..=(diff.x.abs()) {
-            //  println!("{i} - X - current={current}");
-            cave[current.y as usize][current.x as usize] = b'#';
-            current.x += 1;
+        if let Some(floor) = self.floor {
+            for x in 0..image.width() {
+                image.set(x, (floor - min.y) as usize, FLOOR_COLOR);
+            }
+        }
+        for falling in falling {
+            let pixel = falling - min;
+            image.set(pixel.x as usize, pixel.y as usize, FALLING_COLOR);
         }
+
+        image
     }
-    // we are drawing from to right so if we are supposed to draw to the left,
-    // we have to go back and draw to right
 
-    if start.y != end.y {
-        // we draw from top to bottom, so if coords are from bottom to top, we have to offset
-        let mut current = if !diff.y.is_negative() { end } else { start };
+    #[cfg(test)]
+    fn render(&self) -> String {
+        self.grid.render_with(|b| char::from(*b))
+    }
+}
 
-        //println!("current={current}");
-        for _ in 0..=(diff.y.abs()) {
-            // println!("{i} - Y - current={current}");
-            cave[current.y as usize][current.x as usize] = b'#';
-            current.y += 1;
-        }
+fn add_stones(grid: &mut SparseGrid<u8>, start: IVec2, end: IVec2) {
+    let step = (end - start).signum();
+    let length = (end - start).abs().max_element();
+
+    for i in 0..=length {
+        grid.set(start + step * i, ROCK);
     }
 }
 
 #[aoc_generator(day14)]
-pub fn parse_input(input: &str) -> (Vec<Vec<IVec2>>, i32) {
+pub fn parse_input(input: &str) -> Result<(Vec<Vec<IVec2>>, i32), ParseError> {
     let mut depth = 0;
     let lines = input
         .lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(index, line)| {
             line.split("->")
-                .filter_map(|splitted| {
-                    splitted.split_once(",").map(|(x, y)| {
-                        let (x, y) = (
-                            x.trim().parse::<i32>().unwrap(),
-                            y.trim().parse::<i32>().unwrap(),
-                        );
-
-                        if y > depth {
-                            depth = y
-                        }
-
-                        IVec2::new(x, y)
-                    })
+                .map(|splitted| {
+                    let Some((x, y)) = splitted.split_once(',') else {
+                        return Err(ParseError::at(
+                            DAY,
+                            index + 1,
+                            line,
+                            splitted.trim(),
+                            "expected coordinates 'x,y'",
+                        ));
+                    };
+                    let (x, y) = (
+                        parse_number::<i32>(DAY, index + 1, line, x.trim())?,
+                        parse_number::<i32>(DAY, index + 1, line, y.trim())?,
+                    );
+
+                    if y > depth {
+                        depth = y
+                    }
+
+                    Ok(IVec2::new(x, y))
                 })
-                .collect::<Vec<IVec2>>()
+                .collect::<Result<Vec<IVec2>, ParseError>>()
         })
-        .collect::<Vec<Vec<IVec2>>>();
+        .collect::<Result<Vec<Vec<IVec2>>, ParseError>>()?;
 
-    (lines, depth)
+    Ok((lines, depth))
+}
+
+/// Pours sand until the cave is full and writes the final state as a single image.
+/// Returns the number of grains which came to rest.
+pub fn export_image(
+    (input, depth): &(Vec<Vec<IVec2>>, i32),
+    with_floor: bool,
+    format: ImageFormat,
+    out: impl Write,
+) -> io::Result<usize> {
+    let mut cave = Cave::new(input, *depth);
+    if with_floor {
+        cave = cave.with_floor();
+    }
+
+    let grains = std::iter::from_fn(|| cave.pour_grain(!with_floor)).count();
+    cave.to_image().write(format, BufWriter::new(out))?;
+
+    Ok(grains)
+}
+
+/// Writes `frame_00000.<ext>`, `frame_00001.<ext>`, ... into `dir`, one frame every `every` grains
+/// starting with the empty cave and ending with the full one. Returns the number of frames written.
+pub fn export_frames(
+    (input, depth): &(Vec<Vec<IVec2>>, i32),
+    with_floor: bool,
+    format: ImageFormat,
+    every: usize,
+    dir: &Path,
+) -> io::Result<usize> {
+    assert!(every > 0, "frames must be at least one grain apart");
+
+    let mut cave = Cave::new(input, *depth);
+    if with_floor {
+        cave = cave.with_floor();
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut frames = 0;
+    let mut write_frame = |cave: &Cave| -> io::Result<()> {
+        let name = format!("frame_{frames:05}.{}", format.extension());
+        let file = File::create(dir.join(name))?;
+        cave.to_image().write(format, BufWriter::new(file))?;
+        frames += 1;
+        Ok(())
+    };
+
+    write_frame(&cave)?;
+    let mut grains = 0;
+    while cave.pour_grain(!with_floor).is_some() {
+        grains += 1;
+        if grains % every == 0 {
+            write_frame(&cave)?;
+        }
+    }
+    if grains % every != 0 {
+        write_frame(&cave)?;
+    }
+
+    Ok(frames)
+}
+
+/// Pours sand following `rules` and returns the number of grains which came to rest.
+/// Without the floor it stops at the first grain falling into the abyss, with the floor
+/// once every source is blocked.
+pub fn simulate(
+    (input, depth): &(Vec<Vec<IVec2>>, i32),
+    rules: &SandRules,
+    with_floor: bool,
+) -> Result<usize, RulesError> {
+    let mut cave = Cave::new(input, *depth).with_rules(rules.clone())?;
+    if with_floor {
+        cave = cave.with_floor();
+    }
+
+    Ok(std::iter::from_fn(|| cave.pour_grain(!with_floor)).count())
 }
 
 #[aoc(day14, part1)]
-pub fn part1((input, depth): &(Vec<Vec<IVec2>>, i32)) -> usize {
-    //println!("running part1");
+pub fn part1(input: &(Vec<Vec<IVec2>>, i32)) -> usize {
+    simulate(input, &SandRules::default(), false).expect("the puzzle rules are valid")
+}
+
+#[aoc(day14, part2)]
+pub fn part2(input: &(Vec<Vec<IVec2>>, i32)) -> usize {
+    simulate(input, &SandRules::default(), true).expect("the puzzle rules are valid")
+}
+
+#[aoc(day14, part2, Analytical)]
+pub fn part2_analytical((input, depth): &(Vec<Vec<IVec2>>, i32)) -> usize {
+    Cave::new(input, *depth).with_floor().count_sand_rows()
+}
+
+#[aoc(day14, part1, Recursive)]
+pub fn part1_recursive((input, depth): &(Vec<Vec<IVec2>>, i32)) -> usize {
     let mut sand_counter = 0;
-    let mut cave = Cave::new(input, *depth as usize);
-    while let Some(sand_stuck_at) = cave.simulate_grain_of_sand(IVec2::new(500, 0), true) {
-        //println!("sand_stuck_at={sand_stuck_at}");
-        cave.inner[sand_stuck_at.y as usize][sand_stuck_at.x as usize] = b'o';
-        /* println!(
-            "stuck_at_position={}",
-            cave.inner[sand_stuck_at.y as usize][sand_stuck_at.x as usize]
-        ); */
+    let mut cave = Cave::new(input, *depth);
+    while let Some(sand_stuck_at) = cave.simulate_grain_of_sand(SOURCE, true) {
+        cave.grid.set(sand_stuck_at, SAND);
         sand_counter += 1;
     }
 
-    //cave.print();
-
     sand_counter
 }
 
-#[aoc(day14, part2)]
-pub fn part2((input, depth): &(Vec<Vec<IVec2>>, i32)) -> usize {
-    todo!("can't use array as it creates fictive bounday")
-    /* let mut sand_counter = 0;
-    let mut cave = Cave::new(input, *depth as usize).add_floor();
-    while let Some(sand_stuck_at) = cave.simulate_grain_of_sand(IVec2::new(500, 0), false) {
-        //println!("sand_stuck_at={sand_stuck_at}");
-        cave.inner[sand_stuck_at.y as usize][sand_stuck_at.x as usize] = b'o';
-        /* println!(
-            "stuck_at_position={}",
-            cave.inner[sand_stuck_at.y as usize][sand_stuck_at.x as usize]
-        ); */
+#[aoc(day14, part2, Recursive)]
+pub fn part2_recursive((input, depth): &(Vec<Vec<IVec2>>, i32)) -> usize {
+    let mut sand_counter = 0;
+    let mut cave = Cave::new(input, *depth).with_floor();
+    while let Some(sand_stuck_at) = cave.simulate_grain_of_sand(SOURCE, false) {
+        cave.grid.set(sand_stuck_at, SAND);
         sand_counter += 1;
-        println!("sand_stuck_at={sand_stuck_at}");
-        if sand_stuck_at.x == 500 && sand_stuck_at.y == 0 {
-            cave.print();
-            return sand_counter;
-        }
-
-        if sand_counter % 500 == 0 {
-            let now = chrono::Local::now();
-            println!("{} still working", now.format("HH:MM:SS"))
-        }
 
-        if sand_counter % 1000 == 0 {
-            println!("DEBUG CAVE ====================================================================================================================");
-            cave.print();
-            println!("===============================================================================================================================");
+        if sand_stuck_at == SOURCE {
+            break;
         }
     }
 
-    cave.print();
-
-    sand_counter */
+    sand_counter
 }
 
+#[cfg(test)]
 const TEST_INPUT: &str = r#"498,4 -> 498,6 -> 496,6
 503,4 -> 502,4 -> 502,9 -> 494,9"#;
 
 #[test]
 fn fill_with_stones_test() {
-    let (stones, depth) = parse_input(TEST_INPUT);
-    let mut cave = Cave::new(&stones, depth as usize);
-    cave.print();
-    panic!()
+    let (stones, depth) = parse_input(TEST_INPUT).unwrap();
+    let cave = Cave::new(&stones, depth);
+    let want = r#"....#...##
+....#...#.
+..###...#.
+........#.
+........#.
+#########.
+"#;
+    assert_eq!(want, cave.render())
 }
 
 #[test]
 fn parse_input_test() {
-    let got = parse_input(TEST_INPUT);
+    let got = parse_input(TEST_INPUT).unwrap();
     let want = vec![
         vec![IVec2::new(498, 4), IVec2::new(498, 6), IVec2::new(496, 6)],
         vec![
@@ -232,13 +528,200 @@ fn parse_input_test() {
             IVec2::new(494, 9),
         ],
     ];
+    assert_eq!((want, 9), got)
+}
+
+#[test]
+fn parse_input_error_test() {
+    let got = parse_input("498,4 -> 498,6\n503,4 -> 502 4").unwrap_err();
+    assert_eq!((2, 10), (got.line, got.column));
+
+    let got = parse_input("498,4 -> 498,x6").unwrap_err();
+    assert_eq!((1, 14), (got.line, got.column));
 }
 
 #[test]
 pub fn part1_test() {
-    let got = part1(&parse_input(TEST_INPUT));
+    let got = part1(&parse_input(TEST_INPUT).unwrap());
     assert_eq!(24, got)
 }
 
 #[test]
-pub fn part2_test() {}
+pub fn part2_test() {
+    let got = part2(&parse_input(TEST_INPUT).unwrap());
+    assert_eq!(93, got)
+}
+
+#[test]
+pub fn part2_wide_cave_test() {
+    // a single rock far away from the source must not bound the pile
+    let got = part2(&parse_input("1000,3 -> 1001,3").unwrap());
+    assert_eq!(25, got)
+}
+
+#[test]
+pub fn recursive_test() {
+    let input = parse_input(TEST_INPUT).unwrap();
+    assert_eq!(24, part1_recursive(&input));
+    assert_eq!(93, part2_recursive(&input));
+}
+
+#[test]
+fn pour_grain_test() {
+    let (stones, depth) = parse_input(TEST_INPUT).unwrap();
+    let mut cave = Cave::new(&stones, depth);
+    assert_eq!(Some(IVec2::new(500, 8)), cave.pour_grain(true));
+    assert_eq!(Some(IVec2::new(499, 8)), cave.pour_grain(true));
+    assert_eq!(Some(IVec2::new(501, 8)), cave.pour_grain(true));
+    // the next grain resumes right above the previous one
+    assert_eq!(Some(&IVec2::new(500, 7)), cave.paths[0].last());
+}
+
+#[test]
+fn simulate_fall_order_test() {
+    // mirrored cave with down-right tried before down-left behaves the same
+    let (stones, depth) = parse_input(TEST_INPUT).unwrap();
+    let mirrored = stones
+        .iter()
+        .map(|stone| {
+            stone
+                .iter()
+                .map(|pos| IVec2::new(2 * SOURCE.x - pos.x, pos.y))
+                .collect()
+        })
+        .collect();
+    let rules = SandRules {
+        fall_order: vec![DOWN, DOWN + RIGHT, DOWN + LEFT],
+        ..SandRules::default()
+    };
+
+    assert_eq!(Ok(24), simulate(&(mirrored, depth), &rules, false));
+}
+
+#[test]
+fn simulate_sources_test() {
+    let input = parse_input("0,3 -> 0,3").unwrap();
+    let rules = |sources| SandRules {
+        sources,
+        ..SandRules::default()
+    };
+
+    assert_eq!(Ok(25), simulate(&input, &SandRules::default(), true));
+    // far apart every source fills its own triangle
+    let far = vec![SOURCE, IVec2::new(1000, 0), SOURCE];
+    assert_eq!(Ok(50), simulate(&input, &rules(far), true));
+    // close sources share the rows below them: 2 + 5 + 7 + 9 + 11
+    let close = vec![SOURCE, IVec2::new(502, 0)];
+    assert_eq!(Ok(34), simulate(&input, &rules(close), true));
+
+    assert_eq!(
+        Err(RulesError::NoSources),
+        simulate(&input, &rules(vec![]), true)
+    );
+}
+
+#[test]
+fn simulate_rules_error_test() {
+    let input = parse_input("498,4 -> 502,4").unwrap();
+    // a grain moving sideways or up could wander without ever settling
+    for offset in [RIGHT, LEFT, IVec2::new(0, -1), IVec2::ZERO] {
+        let rules = SandRules {
+            fall_order: vec![DOWN, offset],
+            ..SandRules::default()
+        };
+        assert_eq!(
+            Err(RulesError::NotFalling(offset)),
+            simulate(&input, &rules, true)
+        );
+    }
+}
+
+#[test]
+fn simulate_spread_test() {
+    let input = parse_input("498,2 -> 502,2").unwrap();
+    let rules = |spread| SandRules {
+        spread,
+        ..SandRules::default()
+    };
+
+    assert_eq!(Ok(4), simulate(&input, &rules(None), false));
+    assert_eq!(Ok(4), simulate(&input, &rules(Some(0)), false));
+    // the second grain rolls off the ledge from (499,1) to (498,1)
+    assert_eq!(Ok(1), simulate(&input, &rules(Some(1)), false));
+    // already the first grain rolls two cells to reach the edge
+    assert_eq!(Ok(0), simulate(&input, &rules(Some(2)), false));
+}
+
+#[test]
+pub fn part2_analytical_test() {
+    let inputs = [
+        TEST_INPUT,
+        "1000,3 -> 1001,3",
+        "500,2 -> 500,2",
+        "497,2 -> 503,2",
+        "495,5 -> 499,5 -> 499,3\n501,3 -> 501,5 -> 505,5\n500,8 -> 500,9",
+    ];
+
+    for input in inputs {
+        let input = parse_input(input).unwrap();
+        assert_eq!(part2(&input), part2_analytical(&input));
+    }
+
+    assert_eq!(93, part2_analytical(&parse_input(TEST_INPUT).unwrap()));
+}
+
+#[test]
+fn to_image_test() {
+    let (stones, depth) = parse_input(TEST_INPUT).unwrap();
+    let cave = Cave::new(&stones, depth);
+    let image = cave.to_image();
+
+    // cropped to x 494..=503 and y 0..=9
+    assert_eq!((10, 10), (image.width(), image.height()));
+    assert_eq!(Some(FALLING_COLOR), image.get(6, 0));
+    assert_eq!(Some(ROCK_COLOR), image.get(4, 4));
+    assert_eq!(Some(AIR_COLOR), image.get(5, 4));
+
+    let mut cave = cave.with_floor();
+    cave.pour_grain(false);
+    let image = cave.to_image();
+    assert_eq!((10, 12), (image.width(), image.height()));
+    assert_eq!(Some(SAND_COLOR), image.get(6, 8));
+    assert_eq!(Some(FALLING_COLOR), image.get(6, 7));
+    assert_eq!(Some(AIR_COLOR), image.get(0, 10));
+    assert!((0..10).all(|x| image.get(x, 11) == Some(FLOOR_COLOR)));
+
+    // cropped to the configured source, 500,0 isn't one
+    let mut cave = Cave::new(&stones, depth)
+        .with_rules(SandRules {
+            sources: vec![IVec2::new(510, 2)],
+            ..SandRules::default()
+        })
+        .unwrap();
+    cave.pour_grain(true);
+    let image = cave.to_image();
+    // x 494..=510 and y 2..=10, the grain fell into the abyss past the deepest rock
+    assert_eq!((17, 9), (image.width(), image.height()));
+    assert_eq!(Some(AIR_COLOR), image.get(16, 0));
+    assert_eq!(Some(FALLING_COLOR), image.get(16, 8));
+}
+
+#[test]
+fn export_test() {
+    let input = parse_input(TEST_INPUT).unwrap();
+
+    let mut ppm = vec![];
+    assert_eq!(
+        24,
+        export_image(&input, false, ImageFormat::Ppm, &mut ppm).unwrap()
+    );
+    assert!(ppm.starts_with(b"P6\n"));
+
+    let dir = std::env::temp_dir().join(format!("aoc-day14-frames-{}", std::process::id()));
+    let frames = export_frames(&input, false, ImageFormat::Png, 10, &dir).unwrap();
+    // empty cave, after 10 and 20 grains and the full one
+    assert_eq!(4, frames);
+    assert!(dir.join("frame_00003.png").exists());
+    assert!(!dir.join("frame_00004.png").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
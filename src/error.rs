@@ -0,0 +1,123 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub day: u8,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(
+        day: u8,
+        line: usize,
+        column: usize,
+        snippet: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        ParseError {
+            day,
+            line,
+            column,
+            snippet: snippet.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// `snippet` is expected to be a subslice of `line`, column is resolved from its position.
+    pub fn at(
+        day: u8,
+        line_number: usize,
+        line: &str,
+        snippet: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self::new(day, line_number, column_of(line, snippet), snippet, message)
+    }
+
+    pub fn end_of_line(
+        day: u8,
+        line_number: usize,
+        line: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self::new(day, line_number, line.chars().count() + 1, "", message)
+    }
+
+    pub fn missing_line(day: u8, line_number: usize, message: impl Into<String>) -> Self {
+        Self::new(day, line_number, 1, "", message)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "day{} line {}, column {}: {}",
+            self.day, self.line, self.column, self.message
+        )?;
+
+        match self.snippet.is_empty() {
+            true => Ok(()),
+            false => write!(f, ", found '{}'", self.snippet),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse_number<T: FromStr>(
+    day: u8,
+    line_number: usize,
+    line: &str,
+    snippet: &str,
+) -> Result<T, ParseError> {
+    snippet
+        .parse::<T>()
+        .map_err(|_| ParseError::at(day, line_number, line, snippet, "expected number"))
+}
+
+/// Character column `snippet` starts at, it has to be a subslice of `line`.
+pub fn column_of(line: &str, snippet: &str) -> usize {
+    let offset = (snippet.as_ptr() as usize).wrapping_sub(line.as_ptr() as usize);
+    debug_assert!(
+        offset <= line.len() && snippet.len() <= line.len() - offset,
+        "snippet {snippet:?} isn't a part of line {line:?}"
+    );
+    line.get(..offset)
+        .map(|before| before.chars().count() + 1)
+        .unwrap_or(1)
+}
+
+#[test]
+fn column_of_test() {
+    let line = "move 1 from 2 to 1";
+    assert_eq!(1, column_of(line, &line[..4]));
+    assert_eq!(13, column_of(line, &line[12..13]));
+    assert_eq!(19, column_of(line, &line[18..]));
+    assert_eq!(4, column_of("žé x", &"žé x"[5..]));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "isn't a part of line")]
+fn column_of_copy_test() {
+    let line = "move 1 from 2 to 1";
+    column_of(line, &String::from("move"));
+}
+
+#[test]
+fn display_test() {
+    let line = "Test: divisible 23";
+    let got = ParseError::at(11, 14, line, line, "expected 'Test: divisible by'").to_string();
+    assert_eq!(
+        "day11 line 14, column 1: expected 'Test: divisible by', found 'Test: divisible 23'",
+        got
+    );
+
+    let got = ParseError::missing_line(5, 3, "expected instructions").to_string();
+    assert_eq!("day5 line 3, column 1: expected instructions", got);
+}
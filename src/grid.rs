@@ -0,0 +1,402 @@
+use glam::IVec2;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+pub const LEFT: IVec2 = IVec2::new(-1, 0);
+pub const UP: IVec2 = IVec2::new(0, -1);
+pub const RIGHT: IVec2 = IVec2::new(1, 0);
+pub const DOWN: IVec2 = IVec2::new(0, 1);
+
+// y grows downwards, so `UP` points to the previous row
+pub const NEIGHBORS_4: [IVec2; 4] = [LEFT, UP, RIGHT, DOWN];
+pub const NEIGHBORS_8: [IVec2; 8] = [
+    LEFT,
+    IVec2::new(-1, -1),
+    UP,
+    IVec2::new(1, -1),
+    RIGHT,
+    IVec2::new(1, 1),
+    DOWN,
+    IVec2::new(-1, 1),
+];
+
+pub trait Storage<T> {
+    fn get(&self, pos: IVec2) -> Option<&T>;
+    fn get_mut(&mut self, pos: IVec2) -> Option<&mut T>;
+    /// Returns `false` if the backend can't hold a cell at `pos`.
+    fn set(&mut self, pos: IVec2, value: T) -> bool;
+    /// Inclusive top-left and bottom-right corners of the stored area.
+    fn bounds(&self) -> (IVec2, IVec2);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dense<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Dense<T> {
+    fn index_of(&self, pos: IVec2) -> Option<usize> {
+        if pos.x < 0 || pos.y < 0 || pos.x as usize >= self.width || pos.y as usize >= self.height {
+            return None;
+        }
+
+        Some(pos.y as usize * self.width + pos.x as usize)
+    }
+}
+
+impl<T> Storage<T> for Dense<T> {
+    fn get(&self, pos: IVec2) -> Option<&T> {
+        self.index_of(pos).map(|index| &self.cells[index])
+    }
+
+    fn get_mut(&mut self, pos: IVec2) -> Option<&mut T> {
+        self.index_of(pos).map(|index| &mut self.cells[index])
+    }
+
+    fn set(&mut self, pos: IVec2, value: T) -> bool {
+        match self.index_of(pos) {
+            Some(index) => {
+                self.cells[index] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounds(&self) -> (IVec2, IVec2) {
+        (
+            IVec2::ZERO,
+            IVec2::new(self.width as i32 - 1, self.height as i32 - 1),
+        )
+    }
+}
+
+/// Unbounded storage, cells which were never set read as `fill`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sparse<T> {
+    cells: HashMap<IVec2, T>,
+    fill: T,
+    min: IVec2,
+    max: IVec2,
+}
+
+impl<T: Clone> Storage<T> for Sparse<T> {
+    fn get(&self, pos: IVec2) -> Option<&T> {
+        Some(self.cells.get(&pos).unwrap_or(&self.fill))
+    }
+
+    fn get_mut(&mut self, pos: IVec2) -> Option<&mut T> {
+        self.min = self.min.min(pos);
+        self.max = self.max.max(pos);
+        Some(self.cells.entry(pos).or_insert_with(|| self.fill.clone()))
+    }
+
+    fn set(&mut self, pos: IVec2, value: T) -> bool {
+        self.min = self.min.min(pos);
+        self.max = self.max.max(pos);
+        self.cells.insert(pos, value);
+        true
+    }
+
+    fn bounds(&self) -> (IVec2, IVec2) {
+        (self.min, self.max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T, S = Dense<T>> {
+    storage: S,
+    marker: PhantomData<T>,
+}
+
+pub type SparseGrid<T> = Grid<T, Sparse<T>>;
+
+impl<T> Grid<T, Dense<T>> {
+    pub fn from_vec(width: usize, cells: Vec<T>) -> Self {
+        assert!(
+            width > 0 && cells.len().is_multiple_of(width),
+            "cells do not form rows of width {width}"
+        );
+
+        Grid {
+            storage: Dense {
+                width,
+                height: cells.len() / width,
+                cells,
+            },
+            marker: PhantomData,
+        }
+    }
+
+    pub fn dense(width: usize, height: usize, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_vec(width, vec![fill; width * height])
+    }
+
+    pub fn index_of(&self, pos: IVec2) -> Option<usize> {
+        self.storage.index_of(pos)
+    }
+
+    pub fn position_of(&self, index: usize) -> IVec2 {
+        IVec2::new(
+            (index % self.storage.width) as i32,
+            (index / self.storage.width) as i32,
+        )
+    }
+
+    pub fn cells(&self) -> &[T] {
+        &self.storage.cells
+    }
+}
+
+impl<T: Clone> Grid<T, Sparse<T>> {
+    pub fn sparse(fill: T) -> Self {
+        Grid {
+            storage: Sparse {
+                cells: HashMap::new(),
+                fill,
+                min: IVec2::splat(i32::MAX),
+                max: IVec2::splat(i32::MIN),
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// Cells which were explicitly set, in no particular order.
+    pub fn stored(&self) -> impl Iterator<Item = (IVec2, &T)> + '_ {
+        self.storage.cells.iter().map(|(pos, value)| (*pos, value))
+    }
+}
+
+impl<T, S: Storage<T>> Grid<T, S> {
+    pub fn get(&self, pos: IVec2) -> Option<&T> {
+        self.storage.get(pos)
+    }
+
+    pub fn get_mut(&mut self, pos: IVec2) -> Option<&mut T> {
+        self.storage.get_mut(pos)
+    }
+
+    pub fn set(&mut self, pos: IVec2, value: T) -> bool {
+        self.storage.set(pos, value)
+    }
+
+    pub fn bounds(&self) -> (IVec2, IVec2) {
+        self.storage.bounds()
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        let (min, max) = self.bounds();
+        pos.cmpge(min).all() && pos.cmple(max).all()
+    }
+
+    pub fn width(&self) -> usize {
+        let (min, max) = self.bounds();
+        (max.x as i64 - min.x as i64 + 1).max(0) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        let (min, max) = self.bounds();
+        (max.y as i64 - min.y as i64 + 1).max(0) as usize
+    }
+
+    pub fn neighbors4(&self, pos: IVec2) -> impl Iterator<Item = (IVec2, &T)> + '_ {
+        self.offsets(pos, &NEIGHBORS_4)
+    }
+
+    pub fn neighbors8(&self, pos: IVec2) -> impl Iterator<Item = (IVec2, &T)> + '_ {
+        self.offsets(pos, &NEIGHBORS_8)
+    }
+
+    fn offsets<'g>(
+        &'g self,
+        pos: IVec2,
+        offsets: &'static [IVec2],
+    ) -> impl Iterator<Item = (IVec2, &'g T)> + 'g {
+        offsets.iter().filter_map(move |offset| {
+            let neighbor = pos + *offset;
+            self.get(neighbor).map(|value| (neighbor, value))
+        })
+    }
+
+    /// Cells from `from` (excluded) in `direction` until the edge of the bounds.
+    /// A zero direction never leaves `from`, so it yields nothing.
+    pub fn ray(&self, from: IVec2, direction: IVec2) -> impl Iterator<Item = (IVec2, &T)> + '_ {
+        let first = Some(from + direction).filter(|_| direction != IVec2::ZERO);
+        std::iter::successors(first, move |pos| Some(*pos + direction))
+            .take_while(|pos| self.contains(*pos))
+            .filter_map(|pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn row(&self, y: i32) -> impl Iterator<Item = &T> + '_ {
+        let (min, max) = self.bounds();
+        (min.x..=max.x).filter_map(move |x| self.get(IVec2::new(x, y)))
+    }
+
+    pub fn column(&self, x: i32) -> impl Iterator<Item = &T> + '_ {
+        let (min, max) = self.bounds();
+        (min.y..=max.y).filter_map(move |y| self.get(IVec2::new(x, y)))
+    }
+
+    /// All positions within bounds, row by row.
+    pub fn positions(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = self.bounds();
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, &T)> + '_ {
+        self.positions()
+            .filter_map(|pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn render_with(&self, f: impl Fn(&T) -> char) -> String {
+        let (min, max) = self.bounds();
+        let mut out = String::with_capacity(self.width() * self.height() + self.height());
+        for y in min.y..=max.y {
+            out.extend(self.row(y).map(&f));
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+impl<T: fmt::Display, S: Storage<T>> fmt::Display for Grid<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (min, max) = self.bounds();
+        for y in min.y..=max.y {
+            for value in self.row(y) {
+                write!(f, "{value}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_grid() -> Grid<u8> {
+    Grid::from_vec(
+        5,
+        vec![
+            0, 1, 2, 3, 4, //
+            5, 6, 7, 8, 9, //
+            10, 11, 12, 13, 14, //
+            15, 16, 17, 18, 19, //
+        ],
+    )
+}
+
+#[test]
+fn index_conversion_test() {
+    let grid = test_grid();
+    assert_eq!(Some(7), grid.index_of(IVec2::new(2, 1)));
+    assert_eq!(IVec2::new(2, 1), grid.position_of(7));
+    assert_eq!(None, grid.index_of(IVec2::new(5, 0)));
+    assert_eq!(None, grid.index_of(IVec2::new(-1, 0)));
+    assert_eq!((5, 4), (grid.width(), grid.height()));
+}
+
+#[test]
+fn neighbors4_test() {
+    let grid = test_grid();
+    let got = grid
+        .neighbors4(IVec2::new(2, 1))
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![6, 2, 8, 12], got);
+
+    let got = grid
+        .neighbors4(IVec2::new(0, 0))
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![1, 5], got);
+
+    let got = grid
+        .neighbors4(IVec2::new(4, 3))
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![18, 14], got);
+}
+
+#[test]
+fn neighbors8_test() {
+    let grid = test_grid();
+    let got = grid
+        .neighbors8(IVec2::new(2, 1))
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![6, 1, 2, 3, 8, 13, 12, 11], got);
+
+    let got = grid.neighbors8(IVec2::new(0, 3)).count();
+    assert_eq!(3, got);
+}
+
+#[test]
+fn ray_test() {
+    let grid = test_grid();
+    let got = grid
+        .ray(IVec2::new(2, 1), RIGHT)
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![8, 9], got);
+
+    let got = grid
+        .ray(IVec2::new(2, 1), UP)
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![2], got);
+
+    let got = grid
+        .ray(IVec2::new(0, 0), IVec2::new(1, 1))
+        .map(|(_, v)| *v)
+        .collect::<Vec<u8>>();
+    assert_eq!(vec![6, 12, 18], got);
+
+    assert_eq!(0, grid.ray(IVec2::new(0, 2), LEFT).count());
+    assert_eq!(0, grid.ray(IVec2::new(2, 1), IVec2::ZERO).count());
+}
+
+#[test]
+fn row_and_column_test() {
+    let grid = test_grid();
+    assert_eq!(
+        vec![&10, &11, &12, &13, &14],
+        grid.row(2).collect::<Vec<&u8>>()
+    );
+    assert_eq!(vec![&3, &8, &13, &18], grid.column(3).collect::<Vec<&u8>>());
+    assert_eq!(0, grid.row(4).count());
+}
+
+#[test]
+fn sparse_test() {
+    let mut grid = SparseGrid::sparse('.');
+    assert_eq!((0, 0), (grid.width(), grid.height()));
+    assert_eq!("", grid.to_string());
+
+    grid.set(IVec2::new(-2, 1), '#');
+    grid.set(IVec2::new(1, 2), '#');
+    assert_eq!((IVec2::new(-2, 1), IVec2::new(1, 2)), grid.bounds());
+    assert_eq!(Some(&'.'), grid.get(IVec2::new(100, 100)));
+    assert_eq!(4, grid.neighbors4(IVec2::new(100, 100)).count());
+    assert_eq!("#...\n...#\n", grid.to_string());
+
+    *grid.get_mut(IVec2::new(0, 0)).unwrap() = 'o';
+    assert_eq!("..o.\n#...\n...#\n", grid.to_string());
+    assert_eq!(3, grid.stored().count());
+}
+
+#[test]
+fn display_test() {
+    let grid = Grid::from_vec(3, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!("123\n456\n", grid.to_string());
+
+    let got = grid.render_with(|v| if v % 2 == 0 { '#' } else { '.' });
+    assert_eq!(".#.\n#.#\n", got);
+}
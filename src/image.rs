@@ -0,0 +1,182 @@
+use std::io::{self, Write};
+
+pub type Rgb = [u8; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Rgb> {
+        (x < self.width && y < self.height).then(|| self.pixels[y * self.width + x])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    pub fn write(&self, format: ImageFormat, out: impl Write) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(out),
+            ImageFormat::Png => self.write_png(out),
+        }
+    }
+
+    pub fn write_ppm(&self, mut out: impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.pixels.concat())
+    }
+
+    // Uncompressed PNG: zlib stream made of stored deflate blocks, no external crates needed.
+    pub fn write_png(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bit depth, truecolor, default compression, filter and no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(&mut out, b"IHDR", &header)?;
+
+        let mut raw = Vec::with_capacity(self.height * (self.width * 3 + 1));
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0); // no filter
+            raw.extend(row.iter().flatten());
+        }
+        write_chunk(&mut out, b"IDAT", &zlib_stored(&raw))?;
+        write_chunk(&mut out, b"IEND", &[])
+    }
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = u16::MAX as usize;
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+        out.push(is_final);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32<'d>(data: impl IntoIterator<Item = &'d u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[test]
+fn checksums_test() {
+    assert_eq!(0xae42_6082, crc32(b"IEND"));
+    assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+}
+
+#[test]
+fn write_ppm_test() {
+    let mut image = Image::new(2, 1, [0, 0, 0]);
+    image.set(1, 0, [255, 128, 1]);
+
+    let mut got = vec![];
+    image.write_ppm(&mut got).unwrap();
+    assert_eq!(b"P6\n2 1\n255\n\x00\x00\x00\xff\x80\x01".to_vec(), got);
+}
+
+#[test]
+fn write_png_test() {
+    let mut image = Image::new(2, 2, [1, 2, 3]);
+    image.set(0, 1, [4, 5, 6]);
+
+    let mut got = vec![];
+    image.write_png(&mut got).unwrap();
+
+    assert_eq!(b"\x89PNG\r\n\x1a\n", &got[..8]);
+    assert_eq!(b"IHDR", &got[12..16]);
+    assert_eq!([0, 0, 0, 2, 0, 0, 0, 2, 8, 2], got[16..26]);
+    assert_eq!(
+        b"\x00\x00\x00\x00IEND\xae\x42\x60\x82",
+        &got[got.len() - 12..]
+    );
+
+    // IDAT holds a single stored block with both filtered rows
+    let idat = &got[33..];
+    assert_eq!(b"IDAT", &idat[4..8]);
+    let zlib = &idat[8..8 + u32::from_be_bytes(idat[..4].try_into().unwrap()) as usize];
+    let raw = [0, 1, 2, 3, 1, 2, 3, 0, 4, 5, 6, 1, 2, 3];
+    assert_eq!([0x78, 0x01, 1, 14, 0, !14, 0xff], zlib[..7]);
+    assert_eq!(raw, zlib[7..21]);
+    assert_eq!(adler32(&raw).to_be_bytes(), zlib[21..]);
+}
@@ -12,6 +12,11 @@ pub mod day11;
 pub mod day12;
 pub mod day13;
 pub mod day14;
+pub mod error;
+pub mod grid;
+pub mod image;
+#[cfg(test)]
+mod random;
 
 extern crate aoc_runner;
 
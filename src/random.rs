@@ -0,0 +1,11 @@
+/// Xorshift generator for randomized tests, inputs only need to differ, not to be good random
+/// numbers. The seed is fixed so failures reproduce.
+pub fn xorshift() -> impl FnMut() -> u64 {
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    }
+}